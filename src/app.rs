@@ -3,27 +3,37 @@ use color_eyre::Result;
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::{Color, Style, Stylize};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Padding, Paragraph, Widget, Wrap};
+use ratatui::widgets::{Block, Padding, Paragraph, Row, Table, Widget, Wrap};
 use ratatui::{DefaultTerminal, Frame};
 use crossterm::event::{Event, EventStream, KeyCode};
-use tokio;
 use tokio_stream::StreamExt;
+use trainer_rs::{CheatDef, CheatMode, FieldValue, FreezeMode, GameSnapshot, GameStatus, GameWorker, PlayerInfo, TrainerError, WinApi};
 
 pub struct App {
     should_quit: bool,
-    winapi: crate::winapi::WinApi,
-    manager: crate::winapi::process::ProcessManager,
+    // 在后台连接和读写游戏进程，界面只显示它发布的最新状态
+    worker: GameWorker,
+    // 修改设置中选中的字段
+    selected: usize,
+    // 正在输入的新数值，不在输入时为 None
+    editing: Option<String>,
+    // 是否显示单位列表，以及单位列表滚动到的位置
+    show_units: bool,
+    unit_offset: usize,
 }
 
 
 impl App {
     const FRAMES_PER_SECOND: f32 = 60.0;
-    pub fn new() -> Self {
-        Self {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
             should_quit: false,
-            winapi: crate::winapi::WinApi::new(),
-            manager: crate::winapi::process::ProcessManager::default(),
-        }
+            worker: GameWorker::spawn(WinApi::try_new()?),
+            selected: 0,
+            editing: None,
+            show_units: false,
+            unit_offset: 0,
+        })
     }
 
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
@@ -31,13 +41,16 @@ impl App {
         let mut interval = tokio::time::interval(period);
         let mut events = EventStream::new();
 
-        while !self.should_quit {
+        let mut result = Ok(());
+        while !self.should_quit && result.is_ok() {
             tokio::select! {
-                _ = interval.tick() => { terminal.draw(|frame| self.render(frame))?; },
+                _ = interval.tick() => { result = terminal.draw(|frame| self.render(frame)).map(|_| ()); },
                 Some(Ok(event)) = events.next() => self.handle_event(&event),
             }
         }
-        Ok(())
+        // 退出前恢复全图等修改写入的值，界面出错时也要恢复
+        self.worker.shutdown().await;
+        Ok(result?)
     }
 
     fn render(&mut self, frame: &mut Frame) {
@@ -46,8 +59,13 @@ impl App {
         let title = Line::from("帝国时代2 修改器 v2026.01.09").centered().bold();
         frame.render_widget(title, title_area);
 
-        let body_layout = Layout::vertical([Constraint::Max(8), Constraint::Fill(1)]);
-        let [help_area, game_area] = body_layout.areas(body_area);
+        let snapshot = self.worker.snapshot();
+        let body_layout = Layout::vertical([
+            Constraint::Max(10),
+            Constraint::Length(snapshot.cheats.len() as u16 + 4),
+            Constraint::Fill(1),
+        ]);
+        let [help_area, cheat_area, game_area] = body_layout.areas(body_area);
 
         // 使用说明
         let help_block = Block::bordered().padding(Padding::new(2, 2, 1, 1)).title("使用说明");
@@ -55,11 +73,31 @@ impl App {
         frame.render_widget(help_block, help_area);
         Paragraph::new(vec![
           Line::raw("按 Q 或 Esc 退出修改器。"),
-          Line::raw("程序会自动修改游戏的四项资源，以及人口上限。"),
+          Line::raw("按 F1-F12 或者 1-9 开启、关闭对应的修改，所有修改默认关闭。"),
+          Line::raw("按 ↑/↓ 选择字段，空格开启、关闭选中的修改，Enter 输入新的数值，M 切换修改方式（设为、写入一次、至少、最多）。"),
+          Line::raw("按 ←/→ 选择要修改的玩家，默认修改本地玩家。按 U 显示该玩家的单位，PgUp/PgDn 翻页。"),
+          Line::raw("修改的数值在当前目录的 trainer.toml 中配置。"),
         ])
           .wrap(Wrap{ trim: true })
           .render(info_area, frame.buffer_mut());
 
+        // 修改设置
+        let cheat_block = Block::bordered().padding(Padding::new(2, 2, 1, 1)).title("修改设置");
+        let list_area = cheat_block.inner(cheat_area);
+        frame.render_widget(cheat_block, cheat_area);
+        self.render_cheats(&snapshot.cheats, list_area, frame);
+
+        let game_area = if self.show_units {
+            let [game_area, unit_area] = Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(game_area);
+            let unit_block = Block::bordered().padding(Padding::horizontal(2)).title("单位列表");
+            let table_area = unit_block.inner(unit_area);
+            frame.render_widget(unit_block, unit_area);
+            self.render_units(&snapshot, table_area, frame);
+            game_area
+        } else {
+            game_area
+        };
+
         // 游戏信息
         let game_block = Block::bordered().padding(Padding::new(2, 2, 1, 1)).title("游戏信息");
 
@@ -69,57 +107,286 @@ impl App {
         self.render_game_info(main_area, frame);
     }
 
-    fn render_game_info(&mut self, area: Rect, frame: &mut Frame) {
-        if self.winapi.game_process.is_none() {
-          let game_info = self.manager.find_game_process(&self.winapi.psapi_lib, &self.winapi.kernel32_lib);
-          if game_info.is_none() {
-              frame.render_widget(Span::styled("游戏程序未运行！", Style::new().fg(Color::Red)), area);
-              return;
-          }
-          let mut game_process = game_info.unwrap();
-          if let Err(_) = self.winapi.set_game_process(game_process) {
-              frame.render_widget(Span::styled("游戏程序未运行！", Style::new().fg(Color::Red)), area);
-              return;
-          }
-        }
-
-        // println!("name: {}, pid: {}", self.winapi.game_process.as_ref().unwrap().name, self.winapi.game_process.as_ref().unwrap().pid);
-
-        let ret = self.winapi.read_game_info();
-        if let Err(err) = ret {
-            frame.render_widget(Span::styled(err.to_string(), Style::new().fg(Color::Red)), area);
+    fn render_units(&mut self, snapshot: &GameSnapshot, area: Rect, frame: &mut Frame) {
+        let units = match &snapshot.units {
+            Some(Ok(units)) => units,
+            Some(Err(err)) => {
+                Self::render_error(err, area, frame);
+                return;
+            }
+            None => {
+                frame.render_widget(Span::raw("正在读取单位..."), area);
+                return;
+            }
+        };
+
+        // 表头和单位数量占两行
+        let page = (area.height as usize).saturating_sub(2).max(1);
+        self.unit_offset = self.unit_offset.min(units.len().saturating_sub(page));
+        let rows = units.iter().skip(self.unit_offset).take(page).map(|unit| {
+            Row::new(vec![
+                unit.id.to_string(),
+                unit.type_id.to_string(),
+                unit.owner.map_or("-".to_string(), |owner| owner.to_string()),
+                format!("{:.0}", unit.hit_points),
+                format!("({:.1}, {:.1})", unit.x, unit.y),
+                unit.action.map_or("-".to_string(), |action| action.to_string()),
+            ])
+        });
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(4),
+            Constraint::Length(8),
+            Constraint::Length(16),
+            Constraint::Length(6),
+        ];
+        let [count_area, table_area] = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(area);
+        if units.is_empty() {
+            frame.render_widget(Span::raw("没有单位"), count_area);
             return;
         }
-        let info = ret.unwrap();
+        let end = (self.unit_offset + page).min(units.len());
+        frame.render_widget(Span::raw(format!("共 {} 个单位，显示第 {}-{} 个", units.len(), self.unit_offset + 1, end)), count_area);
+        let header = Row::new(vec!["编号", "类型", "玩家", "生命值", "坐标", "行动"]).style(Style::new().bold());
+        frame.render_widget(Table::new(rows, widths).header(header), table_area);
+    }
+
+    fn render_cheats(&self, cheats: &[CheatDef], area: Rect, frame: &mut Frame) {
+        let rows: Vec<Line> = cheats.iter().enumerate().map(|(i, cheat)| {
+            let value = match &self.editing {
+                Some(input) if i == self.selected => format!("{}_", input),
+                _ => cheat.value.to_string(),
+            };
+            // 超过 F12 的修改只能选中之后按空格开关
+            let hotkey = if i < 12 { format!("F{}", i + 1) } else { String::new() };
+            let text = format!(
+                "{} {:<3} [{}] {}: {} {}",
+                if i == self.selected { ">" } else { " " },
+                hotkey,
+                if cheat.enabled { "开" } else { "关" },
+                cheat.field,
+                Self::mode_text(cheat.mode),
+                value,
+            );
+            if i == self.selected {
+                Line::styled(text, Style::new().fg(Color::Cyan))
+            } else {
+                Line::raw(text)
+            }
+        }).collect();
+        Paragraph::new(rows).render(area, frame.buffer_mut());
+    }
+
+    fn mode_text(mode: CheatMode) -> &'static str {
+        match mode {
+            CheatMode::Set => "设为",
+            CheatMode::SetOnce => "写入一次",
+            CheatMode::AtLeast => "至少",
+            CheatMode::AtMost => "最多",
+        }
+    }
+
+    fn render_game_info(&mut self, area: Rect, frame: &mut Frame) {
+        let snapshot = self.worker.snapshot();
+        let info = match &snapshot.status {
+            GameStatus::NotFound => {
+                frame.render_widget(Span::styled("游戏程序未运行！", Style::new().fg(Color::Red)), area);
+                return;
+            }
+            GameStatus::Error(err) => {
+                Self::render_error(err, area, frame);
+                return;
+            }
+            GameStatus::Ready(info) => info,
+        };
         if !info.is_running {
             frame.render_widget(Span::styled("还未开始游戏！", Style::new().fg(Color::Yellow)), area);
             return;
         }
 
-        if let Err(_) = self.winapi.write_game_info() {
-          // 忽略错误
+        let mut rows = vec![Line::raw(format!("游戏进程: {}", info.pid))];
+        for field in &info.fields {
+            rows.push(Line::raw(Self::field_text(&snapshot, field)));
         }
 
-        let rows = vec![
-            Line::raw(format!("游戏进程: {}", info.pid)),
-            Line::raw(format!("木材: {}", info.wood)),
-            Line::raw(format!("食物 {}", info.food)),
-            Line::raw(format!("黄金 {}", info.gold)),
-            Line::raw(format!("石料 {}", info.stone)),
-            Line::raw(format!("人口上限 无限制")),
-        ];
+        // 每个玩家一行，正在修改的玩家在下面列出每个字段的修改状态
+        let target = snapshot.player.or(info.local_player().map(|player| player.index));
+        for player in &info.players {
+            let values: Vec<String> = player.fields.iter().map(|field| format!("{} {}", field.label, field.value)).collect();
+            let text = format!(
+                "{} {}{}: {}",
+                if Some(player.index) == target { ">" } else { " " },
+                Self::player_name(player),
+                Self::player_meta(player),
+                values.join("  "),
+            );
+            if Some(player.index) == target {
+                rows.push(Line::styled(text, Style::new().fg(Color::Cyan)));
+            } else {
+                rows.push(Line::raw(text));
+            }
+        }
+        if let Some(player) = target.and_then(|index| info.player(index)) {
+            rows.push(Line::raw(format!("正在修改 {}：", Self::player_name(player))));
+            for field in &player.fields {
+                rows.push(Line::raw(format!("  {}", Self::field_text(&snapshot, field))));
+            }
+        }
         Paragraph::new(rows)
           .wrap(Wrap{ trim: true })
           .render(area, frame.buffer_mut());
     }
 
+    fn player_name(player: &PlayerInfo) -> String {
+        let mut name = match (&player.name, player.index) {
+            (Some(name), index) if !name.is_empty() => format!("{}. {}", index, name),
+            (_, 0) if !player.is_local => "盖亚".to_string(),
+            (_, index) => format!("玩家 {}", index),
+        };
+        if player.is_local {
+            name.push_str("（本地）");
+        }
+        name
+    }
+
+    // 文明、颜色、队伍以及人类玩家还是电脑，没有读取到的信息不显示
+    fn player_meta(player: &PlayerInfo) -> String {
+        let mut meta = vec![];
+        if let Some(civilization) = &player.civilization {
+            meta.push(civilization.clone());
+        }
+        if let Some(color) = &player.color {
+            meta.push(format!("颜色 {}", color));
+        }
+        if let Some(team) = player.team {
+            meta.push(if team == 0 { "无队伍".to_string() } else { format!("队伍 {}", team) });
+        }
+        if let Some(is_human) = player.is_human {
+            meta.push(if is_human { "玩家" } else { "电脑" }.to_string());
+        }
+        if meta.is_empty() {
+            return String::new();
+        }
+        format!(" [{}]", meta.join("，"))
+    }
+
+    // 字段的数值以及修改状态
+    fn field_text(snapshot: &GameSnapshot, field: &FieldValue) -> String {
+        let mut text = format!("{}: {}", field.label, field.value);
+        if snapshot.cheats.iter().any(|cheat| cheat.field == field.name && !cheat.enabled) {
+            text.push_str("（已关闭）");
+        } else if let Some(entry) = snapshot.freezes.iter().find(|entry| entry.name == field.name)
+          && let Some(cheat) = snapshot.cheats.iter().find(|cheat| cheat.field == field.name)
+          && entry.mode != FreezeMode::Off {
+            text.push_str(&format!("（{} {}，已写入 {} 次", Self::mode_text(cheat.mode), cheat.value, entry.stats.writes));
+            if entry.stats.failures > 0 {
+                text.push_str(&format!("，失败 {} 次", entry.stats.failures));
+            }
+            text.push('）');
+        }
+        text
+    }
+
+    fn render_error(err: &TrainerError, area: Rect, frame: &mut Frame) {
+        let line = match err {
+            TrainerError::ProcessNotFound(_) => Span::styled("游戏程序未运行！", Style::new().fg(Color::Red)),
+            TrainerError::AccessDenied(_) if cfg!(windows) => {
+                Span::styled("没有权限访问游戏进程，请以管理员身份运行修改器！", Style::new().fg(Color::Red))
+            }
+            TrainerError::AccessDenied(_) => {
+                Span::styled("没有权限访问游戏进程，请以 root 身份运行修改器，或者设置 kernel.yama.ptrace_scope=0！", Style::new().fg(Color::Red))
+            }
+            TrainerError::UnknownVersion(fingerprint) => {
+                let lines = vec![
+                    Line::styled("不支持的游戏版本！", Style::new().fg(Color::Red)),
+                    Line::raw(fingerprint.clone()),
+                    Line::raw("请在 profiles 目录中添加该版本的配置。"),
+                ];
+                Paragraph::new(lines)
+                  .wrap(Wrap{ trim: true })
+                  .render(area, frame.buffer_mut());
+                return;
+            }
+            // 游戏正在加载，下一帧重试
+            err if err.is_retryable() => Span::styled("正在读取游戏数据...", Style::new().fg(Color::Yellow)),
+            err => Span::styled(err.to_string(), Style::new().fg(Color::Red)),
+        };
+        Paragraph::new(line)
+          .wrap(Wrap{ trim: true })
+          .render(area, frame.buffer_mut());
+    }
+
     fn handle_event(&mut self, event: &Event) {
-        if let Some(key) = event.as_key_press_event() {
+        let Some(key) = event.as_key_press_event() else {
+            return;
+        };
+        let cheats = self.worker.snapshot().cheats;
+
+        // 输入数值时，Enter 确认，Esc 取消
+        if let Some(input) = &mut self.editing {
             match key.code {
-                KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
-                KeyCode::Esc | KeyCode::Esc => self.should_quit = true,
+                KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || c == '-' => input.push(c),
+                KeyCode::Backspace => { input.pop(); }
+                KeyCode::Enter => {
+                    if let (Ok(value), Some(cheat)) = (input.parse::<f64>(), cheats.get(self.selected))
+                      && value.is_finite() {
+                        self.worker.set_cheat(CheatDef { value, ..cheat.clone() });
+                    }
+                    self.editing = None;
+                }
+                KeyCode::Esc => self.editing = None,
                 _ => {}
             }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::F(n @ 1..=12) => self.toggle_cheat(&cheats, n as usize - 1),
+            KeyCode::Char(c @ '1'..='9') => self.toggle_cheat(&cheats, c as usize - '1' as usize),
+            KeyCode::Char(' ') => self.toggle_cheat(&cheats, self.selected),
+            KeyCode::Char('u') => {
+                self.show_units = !self.show_units;
+                self.unit_offset = 0;
+                self.worker.show_units(self.show_units);
+            }
+            // 翻页的行数在绘制时按表格高度修正
+            KeyCode::PageDown => self.unit_offset += 10,
+            KeyCode::PageUp => self.unit_offset = self.unit_offset.saturating_sub(10),
+            KeyCode::Left => self.select_player(-1),
+            KeyCode::Right => self.select_player(1),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(cheats.len().saturating_sub(1)),
+            KeyCode::Enter if self.selected < cheats.len() => self.editing = Some(String::new()),
+            KeyCode::Char('m') => {
+                if let Some(cheat) = cheats.get(self.selected) {
+                    self.worker.set_cheat(CheatDef { mode: cheat.mode.next(), ..cheat.clone() });
+                }
+            }
+            _ => {}
         }
-    }  
+    }
+
+    // 切换到前一个或者后一个玩家，选择本地玩家时使用本地玩家的指针
+    fn select_player(&self, step: isize) {
+        let snapshot = self.worker.snapshot();
+        let GameStatus::Ready(info) = &snapshot.status else {
+            return;
+        };
+        if info.players.is_empty() {
+            return;
+        }
+        let current = snapshot.player.or(info.local_player().map(|player| player.index));
+        let position = info.players.iter().position(|player| Some(player.index) == current).unwrap_or(0);
+        let next = &info.players[(position as isize + step).rem_euclid(info.players.len() as isize) as usize];
+        self.worker.select_player((!next.is_local).then_some(next.index));
+    }
+
+    // 开启或关闭第 index 个修改
+    fn toggle_cheat(&self, cheats: &[CheatDef], index: usize) {
+        if let Some(cheat) = cheats.get(index) {
+            self.worker.toggle_cheat(&cheat.field);
+        }
+    }
 }
\ No newline at end of file
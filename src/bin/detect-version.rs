@@ -0,0 +1,35 @@
+use trainer_rs::{ProcessManager, WinApi};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  if let Err(e) = dll_api.set_game_process(game_process) {
+    println!("Failed to inject into process: {}", e);
+    return Ok(());
+  }
+  if let Some(bitness) = dll_api.bitness() {
+    println!("Pointer size: {} bytes", bitness.pointer_size());
+  }
+  for module in dll_api.modules() {
+    println!("Module 0x{:X} (0x{:X} bytes) - {}", module.base, module.size, module.name);
+  }
+
+  match dll_api.fingerprint() {
+    Ok(fingerprint) => println!("[detect]\n{}", fingerprint.to_string().replace(", ", "\n")),
+    Err(e) => println!("Failed to read executable header: {}", e),
+  }
+  match &dll_api.layout {
+    Some(layout) => println!("Profile: {}", layout.name),
+    None => println!("Profile: (unsupported version)"),
+  }
+
+  Ok(())
+}
@@ -0,0 +1,75 @@
+use std::io::{self, BufRead, Write};
+
+use trainer_rs::{ProcessManager, TrainerError, UnitEditor, UnitSelector, WinApi};
+
+const USAGE: &str = "usage: edit-unit <attribute> <value> (--unit <id> | --type <type id>) [--player <index>]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let (Some(attribute), Some(value)) = (args.first(), args.get(1)) else {
+    println!("{}", USAGE);
+    return Ok(());
+  };
+  let value: f64 = value.parse().map_err(|_| format!("invalid value: {}", value))?;
+  let mut selector = None;
+  let mut player = None;
+  let mut options = args[2..].iter();
+  while let Some(option) = options.next() {
+    let arg = options.next().ok_or(USAGE)?;
+    let number: i64 = arg.parse().map_err(|_| format!("invalid number: {}", arg))?;
+    match option.as_str() {
+      "--unit" => selector = Some(UnitSelector::Unit(number as i32)),
+      "--type" => selector = Some(UnitSelector::Type(number as i32)),
+      "--player" => player = Some(number as usize),
+      _ => return Err(USAGE.into()),
+    }
+  }
+  let Some(selector) = selector else {
+    println!("{}", USAGE);
+    return Ok(());
+  };
+
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
+  }
+
+  let units = dll_api.read_units(player)?;
+  let (Some(memory), Some(layout)) = (dll_api.memory(), dll_api.layout.as_ref()) else {
+    println!("Game is not started");
+    return Ok(());
+  };
+
+  let mut editor = UnitEditor::new(player);
+  let written = editor.set(memory, layout, &units, selector, attribute, value)?;
+  println!("{} of {} units matched, {} addresses written", units.iter().filter(|unit| selector.matches(unit)).count(), units.len(), written);
+
+  // 类型属性在退出前恢复，生命值的修改不恢复
+  if editor.is_modified() {
+    print!("Press Enter to restore the unit type attributes and exit...");
+    // 读取输入失败时也要先恢复
+    let input = io::stdout().flush().and_then(|_| io::stdin().lock().read_line(&mut String::new()));
+    let restored = editor.restore(memory, layout)?;
+    println!("{} addresses restored", restored);
+    input?;
+  }
+
+  Ok(())
+}
@@ -1,15 +1,13 @@
-use tokio;
-use trainer_rs::winapi;
+use trainer_rs::{ProcessManager, WinApi};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>>{
-  let psapi_lib = winapi::load_psapi_library();
-  let kernel32_lib = winapi::load_kernel32_library();
-  let manager = winapi::process::ProcessManager::default();
-  let list = manager.get_process_list(&psapi_lib, &kernel32_lib);
+  let dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+  let list = dll_api.get_process_list(&manager);
 
   for p in list {
     println!("Process {} - {}", p.pid, p.name);
   }
   Ok(())
-}
\ No newline at end of file
+}
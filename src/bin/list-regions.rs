@@ -0,0 +1,42 @@
+use trainer_rs::{ProcessManager, RegionState, TrainerError, WinApi};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
+  }
+
+  for region in dll_api.regions() {
+    // 未使用的地址空间不显示
+    if region.state == RegionState::Free {
+      continue;
+    }
+    println!(
+      "0x{:016X}-0x{:016X} {:<4} {:<9} {}",
+      region.base,
+      region.end(),
+      region.protection.to_string(),
+      format!("{:?}", region.state),
+      region.module.as_deref().unwrap_or(""),
+    );
+  }
+
+  Ok(())
+}
@@ -0,0 +1,67 @@
+use trainer_rs::{ProcessManager, TrainerError, WinApi};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  // 可选参数：玩家在玩家数组中的下标，不填写时列出所有玩家的单位
+  let player = match std::env::args().nth(1) {
+    Some(arg) => Some(arg.parse::<usize>().map_err(|_| format!("invalid player index: {}", arg))?),
+    None => None,
+  };
+
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
+  }
+
+  let players: Vec<Option<usize>> = match player {
+    Some(index) => vec![Some(index)],
+    None => {
+      let info = dll_api.read_game_info()?;
+      if !info.is_running {
+        println!("Game is not started");
+        return Ok(());
+      }
+      info.players.iter().map(|player| (!player.is_local).then_some(player.index)).collect()
+    }
+  };
+
+  for player in players {
+    let units = dll_api.read_units(player)?;
+    match player {
+      Some(index) => println!("Player {}: {} units", index, units.len()),
+      None => println!("Local player: {} units", units.len()),
+    }
+    println!("{:>10} {:>8} {:>6} {:>5} {:>8} {:>8} {:>8} {:>6}", "address", "id", "type", "owner", "hp", "x", "y", "action");
+    for unit in units {
+      println!(
+        "0x{:08X} {:>8} {:>6} {:>5} {:>8.1} {:>8.2} {:>8.2} {:>6}",
+        unit.address,
+        unit.id,
+        unit.type_id,
+        unit.owner.map_or("-".to_string(), |owner| owner.to_string()),
+        unit.hit_points,
+        unit.x,
+        unit.y,
+        unit.action.map_or("-".to_string(), |action| action.to_string()),
+      );
+    }
+  }
+
+  Ok(())
+}
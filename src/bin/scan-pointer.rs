@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use trainer_rs::scan::pointer;
+use trainer_rs::{PointerScanner, ProcessManager, TrainerError, WinApi};
+
+// 最多显示的结果数，全部结果保存在文件中
+const MAX_PRINT: usize = 20;
+
+const USAGE: &str = "Usage: scan-pointer <address> <output file> [previous results] [--depth N] [--offset 0xN]
+  Find pointer chains from static module addresses to <address> and save them to <output file>.
+  With [previous results] (saved in an earlier game session), only chains that still point to
+  <address> are kept, which removes the unstable ones.";
+
+struct Args {
+  target: usize,
+  output: PathBuf,
+  previous: Option<PathBuf>,
+  scanner: PointerScanner,
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+  let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+  usize::from_str_radix(digits, 16).ok()
+}
+
+fn parse_args() -> Option<Args> {
+  let mut scanner = PointerScanner::default();
+  let mut positional = vec![];
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--depth" => scanner.max_depth = args.next()?.parse().ok()?,
+      "--offset" => scanner.max_offset = parse_hex(&args.next()?)?,
+      _ => positional.push(arg),
+    }
+  }
+
+  let mut positional = positional.into_iter();
+  Some(Args {
+    target: parse_hex(&positional.next()?)?,
+    output: PathBuf::from(positional.next()?),
+    previous: positional.next().map(PathBuf::from),
+    scanner,
+  })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  let Some(args) = parse_args() else {
+    println!("{}", USAGE);
+    return Ok(());
+  };
+
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
+  }
+  let Some(memory) = dll_api.memory() else {
+    return Ok(());
+  };
+
+  let chains = match &args.previous {
+    // 上次的结果在本次进程中重新计算，不需要再扫描
+    Some(previous) => {
+      let previous = pointer::load_chains(previous)?;
+      let chains = pointer::filter_valid(memory, &previous, args.target);
+      println!("{} of {} chains still point to 0x{:X}", chains.len(), previous.len(), args.target);
+      chains
+    }
+    None => {
+      println!(
+        "Scanning pointers to 0x{:X} (depth {}, offset 0x{:X})...",
+        args.target, args.scanner.max_depth, args.scanner.max_offset
+      );
+      let chains = args.scanner.scan(memory, args.target)?;
+      println!("{} chains found", chains.len());
+      chains
+    }
+  };
+
+  for chain in chains.iter().take(MAX_PRINT) {
+    println!("{}", chain);
+  }
+  pointer::save_chains(&args.output, &chains)?;
+  println!("Saved to {}", args.output.display());
+
+  Ok(())
+}
@@ -0,0 +1,121 @@
+use std::io::{self, BufRead, Write};
+
+use trainer_rs::{ProcessManager, ScanFilter, TrainerError, ValueScanner, ValueType, WinApi};
+
+// list 命令最多显示的结果数
+const MAX_LIST: usize = 20;
+
+const USAGE: &str = "Usage: scan-value <type> <value | min..max>
+  type: u8 i8 u16 i16 u32 i32 u64 i64 f32 f64
+
+Commands after the first scan:
+  <value>       exact value, f32/f64 match at the entered precision (200 matches 199.5..200.5)
+  <min>..<max>  value in range
+  changed / unchanged / increased / decreased
+  list          show results
+  new <value | min..max>  start a new scan
+  q             quit";
+
+fn parse_value_type(text: &str) -> Option<ValueType> {
+  let value_type = match text {
+    "u8" => ValueType::U8,
+    "i8" => ValueType::I8,
+    "u16" => ValueType::U16,
+    "i16" => ValueType::I16,
+    "u32" => ValueType::U32,
+    "i32" => ValueType::I32,
+    "u64" => ValueType::U64,
+    "i64" => ValueType::I64,
+    "f32" => ValueType::F32,
+    "f64" => ValueType::F64,
+    _ => return None,
+  };
+  Some(value_type)
+}
+
+fn parse_filter(text: &str) -> Option<ScanFilter> {
+  let filter = match text {
+    "changed" => ScanFilter::Changed,
+    "unchanged" => ScanFilter::Unchanged,
+    "increased" | "+" => ScanFilter::Increased,
+    "decreased" | "-" => ScanFilter::Decreased,
+    _ => match text.split_once("..") {
+      Some((min, max)) => ScanFilter::Range(min.trim().parse().ok()?, max.trim().parse().ok()?),
+      None => ScanFilter::Exact(text.trim_start_matches('=').trim().parse().ok()?),
+    },
+  };
+  Some(filter)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+  let args: Vec<String> = std::env::args().collect();
+  let (Some(value_type), Some(filter)) = (
+    args.get(1).and_then(|text| parse_value_type(text)),
+    args.get(2).and_then(|text| parse_filter(text)),
+  ) else {
+    println!("{}", USAGE);
+    return Ok(());
+  };
+
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
+
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
+    println!("Cannot find Age of Empires II process");
+    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
+  }
+  let Some(memory) = dll_api.memory() else {
+    return Ok(());
+  };
+
+  let mut scanner = ValueScanner::new(value_type);
+  let count = scanner.first_scan(memory, filter)?;
+  println!("{} results", count);
+
+  let stdin = io::stdin();
+  loop {
+    print!("> ");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if stdin.lock().read_line(&mut line)? == 0 {
+      break;
+    }
+
+    let command = line.trim();
+    let result = match command {
+      "" => continue,
+      "q" | "quit" => break,
+      "list" => {
+        for (addr, value) in scanner.results().take(MAX_LIST) {
+          println!("0x{:X} = {}", addr, value);
+        }
+        continue;
+      }
+      _ => match command.strip_prefix("new ") {
+        Some(text) => parse_filter(text).map(|filter| scanner.first_scan(memory, filter)),
+        None => parse_filter(command).map(|filter| scanner.next_scan(memory, filter)),
+      },
+    };
+    match result {
+      Some(Ok(count)) => println!("{} results", count),
+      Some(Err(e)) => println!("Scan failed: {}", e),
+      None => println!("{}", USAGE),
+    }
+  }
+
+  Ok(())
+}
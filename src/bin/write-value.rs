@@ -1,33 +1,33 @@
-use tokio;
-use trainer_rs::winapi;
+use trainer_rs::{ProcessManager, TrainerError, WinApi};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>>{
-  let mut dll_api = winapi::WinApi::new();
-  let manager = winapi::process::ProcessManager::default();
-  let list = manager.get_process_list(&dll_api.psapi_lib, &dll_api.kernel32_lib);
+  let mut dll_api = WinApi::try_new()?;
+  let manager = ProcessManager::default();
 
-  let mut game_process: Option<winapi::process::ProcessItem> = None;
-  for p in list {
-    if p.name.starts_with("age2") && p.name.ends_with(".exe") {
-      println!("Process {} - {}", p.pid, p.name);
-      game_process = Some(p);
-      break;
-    }
-  }
-
-  if game_process.is_none() {
+  let Some(game_process) = dll_api.find_game_process(&manager) else {
     println!("Cannot find Age of Empires II process");
     return Ok(());
-  }
-
-  let mut game_process = game_process.unwrap();
-  if let Err(e) = dll_api.set_game_process(game_process) {
-    println!("Failed to inject into process: {}", e);
-    return Ok(());
+  };
+
+  println!("Process {} - {}", game_process.pid, game_process.name);
+  match dll_api.set_game_process(game_process) {
+    Ok(()) => {}
+    Err(TrainerError::AccessDenied(code)) => {
+      println!("Access denied (os error {}), please run as administrator or root", code);
+      return Ok(());
+    }
+    Err(e) => {
+      println!("Failed to inject into process: {}", e);
+      return Ok(());
+    }
   }
 
   println!("开始修改游戏内存数据...");
+  // 只写入一次，写入配置中的所有字段
+  for cheat in &mut dll_api.config.cheats {
+    cheat.enabled = true;
+  }
 
   if let Err(e) = dll_api.write_game_info() {
     println!("Failed to write game info: {}", e);
@@ -38,4 +38,4 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
   println!("Game Info: {:?}", info);
 
   Ok(())
-}
\ No newline at end of file
+}
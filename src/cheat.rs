@@ -0,0 +1,46 @@
+use crate::config::CheatDef;
+use crate::error::TrainerError;
+use crate::freeze::FreezeEngine;
+use crate::layout::Layout;
+use crate::value::Value;
+use crate::winapi::memory::ProcessMemory;
+
+/*
+  按配置在锁定引擎中锁定玩家的资源数据，布局中没有的字段和关闭的修改会被忽略
+*/
+pub fn apply_cheats(engine: &mut FreezeEngine, cheats: &[CheatDef]) {
+  for cheat in cheats {
+    engine.set(&cheat.field, cheat.freeze_mode(), cheat.interval());
+  }
+}
+
+/*
+  按配置修改玩家的资源数据，关闭的修改会被忽略：先读取整块内存，修改其中的字段后只把变化的部分一次写回
+*/
+pub fn write_game_value(memory: &dyn ProcessMemory, layout: &Layout, cheats: &[CheatDef]) -> Result<(), TrainerError> {
+  for block in layout.blocks() {
+    let (addr, data) = match block.read(memory) {
+      Ok(result) => result,
+      // 游戏程序已运行，但是还未进入游戏状态
+      Err(TrainerError::NullPointer { .. }) => return Ok(()),
+      Err(e) => return Err(e),
+    };
+
+    let mut patched = data.clone();
+    for &(index, offset) in &block.fields {
+      let field = &layout.fields[index];
+      let Some(cheat) = cheats.iter().find(|cheat| cheat.field == field.name) else {
+        continue;
+      };
+      let range = offset..offset + field.value_type.size();
+      let current = Value::from_bytes(field.value_type, &patched[range.clone()])?.as_f64();
+      let Some(value) = cheat.freeze_mode().target(current) else {
+        continue;
+      };
+      patched[range].copy_from_slice(&Value::from_f64(field.value_type, value).to_bytes());
+    }
+    block.write_changes(memory, addr, &data, &patched)?;
+  }
+
+  Ok(())
+}
@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::TrainerError;
+use crate::freeze::FreezeMode;
+use crate::layout::{Layout, ToggleDef};
+use crate::profile;
+
+// 程序内置的配置
+const BUILTIN_CONFIG: &str = include_str!("../config/trainer.toml");
+// 在当前目录和程序所在目录中查找的配置文件，存在时代替内置配置
+const CONFIG_FILE_NAME: &str = "trainer.toml";
+// 默认的写入间隔（毫秒）
+const DEFAULT_INTERVAL_MS: u64 = 250;
+
+
+/*
+  字段的修改方式
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheatMode {
+  // 设为该值
+  Set,
+  // 只写入一次，之后不再修改
+  SetOnce,
+  // 小于该值时改为该值
+  AtLeast,
+  // 大于该值时改为该值
+  AtMost,
+}
+
+impl CheatMode {
+  /*
+    依次切换 设为 -> 写入一次 -> 至少 -> 最多
+  */
+  pub fn next(self) -> Self {
+    match self {
+      CheatMode::Set => CheatMode::SetOnce,
+      CheatMode::SetOnce => CheatMode::AtLeast,
+      CheatMode::AtLeast => CheatMode::AtMost,
+      CheatMode::AtMost => CheatMode::Set,
+    }
+  }
+}
+
+/*
+  一个字段的修改配置
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheatDef {
+  pub field: String,
+  pub mode: CheatMode,
+  pub value: f64,
+  #[serde(default = "default_interval_ms")]
+  pub interval_ms: u64,
+  // 是否开启，关闭时不修改该字段
+  #[serde(default)]
+  pub enabled: bool,
+}
+
+fn default_interval_ms() -> u64 {
+  DEFAULT_INTERVAL_MS
+}
+
+impl CheatDef {
+  pub fn new(field: &str, mode: CheatMode, value: f64) -> Self {
+    Self { field: field.to_string(), mode, value, interval_ms: DEFAULT_INTERVAL_MS, enabled: false }
+  }
+
+  /*
+    锁定引擎中对应的修改方式，关闭时为 Off
+  */
+  pub fn freeze_mode(&self) -> FreezeMode {
+    if !self.enabled {
+      return FreezeMode::Off;
+    }
+    match self.mode {
+      CheatMode::Set => FreezeMode::Freeze(self.value),
+      CheatMode::SetOnce => FreezeMode::SetOnce(self.value),
+      CheatMode::AtLeast => FreezeMode::AtLeast(self.value),
+      CheatMode::AtMost => FreezeMode::AtMost(self.value),
+    }
+  }
+
+  pub fn interval(&self) -> Duration {
+    Duration::from_millis(self.interval_ms)
+  }
+
+  /*
+    检查开关类修改的配置：只能使用 set 或 set_once，有倍数时数值必须大于 0。没有倍数时数值不使用
+  */
+  pub fn check_toggle(&self, toggle: &ToggleDef) -> Result<(), TrainerError> {
+    if !matches!(self.mode, CheatMode::Set | CheatMode::SetOnce) {
+      return Err(TrainerError::Config(format!("toggle \"{}\" only supports mode \"set\" or \"set_once\"", self.field)));
+    }
+    if toggle.scaled() && self.value <= 0.0 {
+      return Err(TrainerError::Config(format!("multiplier of toggle \"{}\" must be greater than 0", self.field)));
+    }
+    Ok(())
+  }
+}
+
+/*
+  修改器的配置，格式参考 config/trainer.toml
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+  #[serde(default, rename = "cheat")]
+  pub cheats: Vec<CheatDef>,
+}
+
+impl Config {
+  pub fn builtin() -> Self {
+    Self::from_toml(BUILTIN_CONFIG).expect("builtin config is invalid")
+  }
+
+  pub fn from_toml(text: &str) -> Result<Self, TrainerError> {
+    let config: Config = toml::from_str(text).map_err(|e| TrainerError::Config(e.to_string()))?;
+    config.validate()?;
+    Ok(config)
+  }
+
+  pub fn load(path: &Path) -> Result<Self, TrainerError> {
+    let text = std::fs::read_to_string(path)?;
+    Self::from_toml(&text).map_err(|e| match e {
+      TrainerError::Config(msg) => TrainerError::Config(format!("{}: {}", path.display(), msg)),
+      e => e,
+    })
+  }
+
+  /*
+    使用当前目录或者程序所在目录中的 trainer.toml，都没有时使用内置配置
+  */
+  pub fn load_default() -> Result<Self, TrainerError> {
+    for dir in profile::search_dirs() {
+      let file = dir.join(CONFIG_FILE_NAME);
+      if file.is_file() {
+        return Self::load(&file);
+      }
+    }
+    Ok(Self::builtin())
+  }
+
+  /*
+    检查配置是否有效：字段名不能为空且不能重复，数值必须是有限的数，写入间隔不能为 0
+  */
+  pub fn validate(&self) -> Result<(), TrainerError> {
+    let mut fields: HashSet<&str> = HashSet::new();
+    for cheat in &self.cheats {
+      if cheat.field.is_empty() {
+        return Err(TrainerError::Config("cheat field must not be empty".to_string()));
+      }
+      if !fields.insert(&cheat.field) {
+        return Err(TrainerError::Config(format!("duplicate cheat for field \"{}\"", cheat.field)));
+      }
+      if !cheat.value.is_finite() {
+        return Err(TrainerError::Config(format!("invalid value for field \"{}\"", cheat.field)));
+      }
+      if cheat.interval_ms == 0 {
+        return Err(TrainerError::Config(format!("interval_ms of field \"{}\" must not be 0", cheat.field)));
+      }
+    }
+    Ok(())
+  }
+
+  /*
+    检查配置中的开关类修改是否符合内存布局中的定义
+  */
+  pub fn validate_layout(&self, layout: &Layout) -> Result<(), TrainerError> {
+    for cheat in &self.cheats {
+      if let Some(toggle) = layout.toggle(&cheat.field) {
+        cheat.check_toggle(toggle)?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn cheat(&self, field: &str) -> Option<&CheatDef> {
+    self.cheats.iter().find(|cheat| cheat.field == field)
+  }
+
+  /*
+    修改字段的配置，没有该字段时添加
+  */
+  pub fn set_cheat(&mut self, cheat: CheatDef) {
+    match self.cheats.iter_mut().find(|c| c.field == cheat.field) {
+      Some(c) => *c = cheat,
+      None => self.cheats.push(cheat),
+    }
+  }
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self::builtin()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn set_once_writes_once() {
+    let config = Config::from_toml("[[cheat]]\nfield = \"food\"\nmode = \"set_once\"\nvalue = 1000\nenabled = true\n").unwrap();
+    let cheat = &config.cheats[0];
+    assert_eq!(cheat.mode, CheatMode::SetOnce);
+    assert_eq!(cheat.freeze_mode(), FreezeMode::SetOnce(1000.0));
+    assert_eq!(CheatDef { enabled: false, ..cheat.clone() }.freeze_mode(), FreezeMode::Off);
+    assert_eq!(CheatMode::Set.next(), CheatMode::SetOnce);
+    assert_eq!(CheatMode::SetOnce.next(), CheatMode::AtLeast);
+
+    let toggle = Layout::builtin().toggles.into_iter().find(|toggle| toggle.name == "reveal_map").unwrap();
+    let cheat = CheatDef { field: "reveal_map".to_string(), mode: CheatMode::SetOnce, ..cheat.clone() };
+    assert!(cheat.check_toggle(&toggle).is_ok());
+    assert!(CheatDef { mode: CheatMode::AtLeast, ..cheat }.check_toggle(&toggle).is_err());
+  }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::io;
+
+
+/*
+  修改器的错误类型，界面和命令行工具可以根据不同的错误做不同的处理：
+  例如读到一部分数据时稍后重试，没有权限时提示以管理员身份运行。
+*/
+#[derive(Debug)]
+pub enum TrainerError {
+  // 找不到进程，或者进程已经退出
+  ProcessNotFound(u32),
+  // 没有权限打开或读写进程，附带系统错误码
+  AccessDenied(i32),
+  // 只读到了一部分数据
+  PartialRead { addr: usize, expected: usize, got: usize },
+  // 只写入了一部分数据
+  PartialWrite { addr: usize, expected: usize, got: usize },
+  // 地址不可访问（未映射或者受保护）
+  InvalidAddress(usize),
+  // 指针链的第 step 步读到了空指针
+  NullPointer { step: usize },
+  // 指针链的第 step 步无法读取地址 addr 中的指针
+  UnreadablePointer { step: usize, addr: usize },
+  // 进程中没有加载该模块
+  ModuleNotFound(String),
+  // 数据长度与类型大小不一致
+  SizeMismatch { expected: usize, got: usize },
+  // 加载系统库或者查找导出函数失败
+  LibraryLoad(String),
+  // 内存布局文件格式错误或者校验失败
+  Layout(String),
+  // 特征码格式错误，或者没有找到唯一的匹配
+  Signature(String),
+  // 扫描条件错误
+  Scan(String),
+  // 配置文件格式错误或者校验失败
+  Config(String),
+  // 没有与游戏执行文件匹配的版本配置，附带执行文件的特征
+  UnknownVersion(String),
+  // 其它系统错误
+  Io(io::Error),
+}
+
+impl TrainerError {
+  /*
+    是否是暂时性的错误，例如游戏正在加载时只读到一部分数据，稍后重试即可
+  */
+  pub fn is_retryable(&self) -> bool {
+    matches!(
+      self,
+      TrainerError::PartialRead { .. }
+        | TrainerError::PartialWrite { .. }
+        | TrainerError::InvalidAddress(_)
+        | TrainerError::NullPointer { .. }
+        | TrainerError::UnreadablePointer { .. }
+    )
+  }
+}
+
+impl fmt::Display for TrainerError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TrainerError::ProcessNotFound(pid) => write!(f, "process {} not found", pid),
+      TrainerError::AccessDenied(code) => write!(f, "access denied (os error {})", code),
+      TrainerError::PartialRead { addr, expected, got } => {
+        write!(f, "read {} of {} bytes at 0x{:X}", got, expected, addr)
+      }
+      TrainerError::PartialWrite { addr, expected, got } => {
+        write!(f, "wrote {} of {} bytes at 0x{:X}", got, expected, addr)
+      }
+      TrainerError::InvalidAddress(addr) => write!(f, "address 0x{:X} is not accessible", addr),
+      TrainerError::NullPointer { step } => write!(f, "null pointer at step {} of pointer chain", step),
+      TrainerError::UnreadablePointer { step, addr } => {
+        write!(f, "cannot read pointer at 0x{:X} (step {} of pointer chain)", addr, step)
+      }
+      TrainerError::ModuleNotFound(name) => write!(f, "module {} not found", name),
+      TrainerError::SizeMismatch { expected, got } => {
+        write!(f, "size mismatch: expected {} bytes, got {}", expected, got)
+      }
+      TrainerError::LibraryLoad(msg) => write!(f, "failed to load library: {}", msg),
+      TrainerError::Layout(msg) => write!(f, "invalid layout: {}", msg),
+      TrainerError::Signature(msg) => write!(f, "signature: {}", msg),
+      TrainerError::Scan(msg) => write!(f, "scan: {}", msg),
+      TrainerError::Config(msg) => write!(f, "invalid config: {}", msg),
+      TrainerError::UnknownVersion(fingerprint) => write!(f, "unsupported game version: {}", fingerprint),
+      TrainerError::Io(err) => err.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for TrainerError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      TrainerError::Io(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<io::Error> for TrainerError {
+  fn from(err: io::Error) -> Self {
+    TrainerError::Io(err)
+  }
+}
+
+impl From<libloading::Error> for TrainerError {
+  fn from(err: libloading::Error) -> Self {
+    TrainerError::LibraryLoad(err.to_string())
+  }
+}
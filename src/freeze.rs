@@ -0,0 +1,628 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::TrainerError;
+use crate::layout::{FieldBlock, Layout, ToggleDef, ToggleTarget};
+use crate::winapi::memory::ProcessMemoryExt;
+use crate::winapi::pointer::{PointerBase, PointerChain};
+use crate::unit;
+use crate::value::Value;
+use crate::winapi::memory::ProcessMemory;
+
+// 默认的写入间隔
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(250);
+// 后台任务两次检查之间的最长等待时间，新设置的锁定最多等待这么久生效
+const IDLE_INTERVAL: Duration = Duration::from_millis(50);
+// 开关类修改中数组的最大长度，读到更大的长度时认为布局有误
+const MAX_ARRAY_LEN: usize = 100000;
+// 无敌模式的名字：锁定玩家所有单位的生命值，数值为生命值占最大生命值的比例
+pub const GOD_MODE: &str = "god_mode";
+
+
+/*
+  字段的修改方式
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreezeMode {
+  // 不修改
+  Off,
+  // 按写入间隔不断写入，把数值锁定在该值
+  Freeze(f64),
+  // 只写入一次，成功后变为 Off
+  SetOnce(f64),
+  // 小于该值时改为该值
+  AtLeast(f64),
+  // 大于该值时改为该值
+  AtMost(f64),
+}
+
+impl FreezeMode {
+  /*
+    根据内存中当前的值，返回需要写入的值，不需要写入时返回 None
+  */
+  pub fn target(&self, current: f64) -> Option<f64> {
+    match *self {
+      FreezeMode::Off => None,
+      FreezeMode::Freeze(value) | FreezeMode::SetOnce(value) => Some(value),
+      FreezeMode::AtLeast(value) => (current < value).then_some(value),
+      FreezeMode::AtMost(value) => (current > value).then_some(value),
+    }
+  }
+
+  /*
+    模式中的数值，Off 时为 None
+  */
+  pub fn value(&self) -> Option<f64> {
+    match *self {
+      FreezeMode::Off => None,
+      FreezeMode::Freeze(value) | FreezeMode::SetOnce(value) | FreezeMode::AtLeast(value) | FreezeMode::AtMost(value) => Some(value),
+    }
+  }
+}
+
+/*
+  字段的写入统计
+*/
+#[derive(Debug, Clone, Default)]
+pub struct WriteStats {
+  // 实际写入的次数，内存中的值已经等于目标值时不会写入
+  pub writes: u64,
+  // 写入失败的次数
+  pub failures: u64,
+  pub last_write: Option<Instant>,
+  pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FreezeEntry {
+  pub name: String,
+  pub mode: FreezeMode,
+  pub interval: Duration,
+  pub stats: WriteStats,
+  // 下次写入的时间
+  next_write: Option<Instant>,
+}
+
+impl FreezeEntry {
+  fn new(name: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      mode: FreezeMode::Off,
+      interval: DEFAULT_INTERVAL,
+      stats: WriteStats::default(),
+      next_write: None,
+    }
+  }
+}
+
+/*
+  锁定引擎：每个字段可以锁定在某个值、只写入一次或者不修改，并且有各自的写入间隔。
+  同一块内存中同时到期的字段合并为一次读取，只写回改变的字段。
+  布局中有单位的最大生命值时，还可以用 GOD_MODE 锁定玩家所有单位的生命值。
+  布局中的开关类修改也按名字设置，关闭之后恢复原来的值
+*/
+#[derive(Debug, Clone)]
+pub struct FreezeEngine {
+  layout: Layout,
+  blocks: Vec<FieldBlock>,
+  // 依次为 layout.fields 中的字段、GOD_MODE（支持无敌模式时）和 layout.toggles 中的开关
+  entries: Vec<FreezeEntry>,
+  // 与 layout.toggles 一一对应，开启之后写入的地址
+  toggle_states: Vec<ToggleState>,
+  // 修改的玩家，None 为本地玩家
+  player: Option<usize>,
+}
+
+impl FreezeEngine {
+  pub fn new(layout: &Layout) -> Self {
+    let mut entries: Vec<FreezeEntry> = layout.fields.iter().map(|field| FreezeEntry::new(&field.name)).collect();
+    if layout.units.as_ref().is_some_and(|units| units.max_hit_points.is_some()) {
+      entries.push(FreezeEntry::new(GOD_MODE));
+    }
+    entries.extend(layout.toggles.iter().map(|toggle| FreezeEntry::new(&toggle.name)));
+    let toggle_states = vec![ToggleState::default(); layout.toggles.len()];
+    Self { layout: layout.clone(), blocks: layout.blocks(), entries, toggle_states, player: None }
+  }
+
+  /*
+    修改玩家数组中第 player 个玩家的锁定引擎，player 为 None 时为本地玩家
+  */
+  pub fn for_player(layout: &Layout, player: Option<usize>, pointer_size: usize) -> Self {
+    let mut engine = Self::new(&layout.with_player(player, pointer_size));
+    engine.player = player;
+    engine
+  }
+
+  /*
+    设置字段的修改方式和写入间隔，立即生效。布局中没有该字段时返回 false
+  */
+  pub fn set(&mut self, name: &str, mode: FreezeMode, interval: Duration) -> bool {
+    let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) else {
+      return false;
+    };
+    entry.mode = mode;
+    entry.interval = interval;
+    entry.next_write = match mode {
+      FreezeMode::Off => None,
+      _ => Some(Instant::now()),
+    };
+    true
+  }
+
+  pub fn entry(&self, name: &str) -> Option<&FreezeEntry> {
+    self.entries.iter().find(|entry| entry.name == name)
+  }
+
+  pub fn entries(&self) -> &[FreezeEntry] {
+    &self.entries
+  }
+
+  /*
+    写入所有到期的字段，返回下一次需要写入的时间，没有需要写入的字段时返回 None
+  */
+  pub fn tick(&mut self, memory: &dyn ProcessMemory, now: Instant) -> Option<Instant> {
+    for block in &self.blocks {
+      let due: Vec<(usize, usize)> = block.fields.iter().copied()
+        .filter(|(index, _)| self.entries[*index].next_write.is_some_and(|next| next <= now))
+        .collect();
+      if due.is_empty() {
+        continue;
+      }
+
+      let result = block.read(memory).and_then(|(addr, data)| {
+        let mut patched = data.clone();
+        let mut changed = vec![];
+        for &(index, offset) in &due {
+          let value_type = self.layout.fields[index].value_type;
+          let range = offset..offset + value_type.size();
+          let current = Value::from_bytes(value_type, &patched[range.clone()])?.as_f64();
+          let Some(value) = self.entries[index].mode.target(current) else {
+            changed.push(false);
+            continue;
+          };
+          let bytes = Value::from_f64(value_type, value).to_bytes();
+          changed.push(patched[range.clone()] != bytes[..]);
+          patched[range].copy_from_slice(&bytes);
+        }
+        block.write_changes(memory, addr, &data, &patched)?;
+        Ok(changed)
+      });
+
+      for (i, &(index, _)) in due.iter().enumerate() {
+        let entry = &mut self.entries[index];
+        entry.next_write = Some(now + entry.interval);
+        match &result {
+          Ok(changed) => {
+            if changed.get(i).copied().unwrap_or(false) {
+              entry.stats.writes += 1;
+              entry.stats.last_write = Some(now);
+            }
+            if let FreezeMode::SetOnce(_) = entry.mode {
+              entry.mode = FreezeMode::Off;
+              entry.next_write = None;
+            }
+          }
+          // 游戏程序已运行，但是还未进入游戏状态，稍后重试
+          Err(TrainerError::NullPointer { .. }) => {}
+          Err(e) => {
+            entry.stats.failures += 1;
+            entry.stats.last_error = Some(e.to_string());
+          }
+        }
+      }
+    }
+
+    self.tick_god_mode(memory, now);
+    self.tick_toggles(memory, now);
+
+    self.entries.iter().filter_map(|entry| entry.next_write).min()
+  }
+
+  fn tick_god_mode(&mut self, memory: &dyn ProcessMemory, now: Instant) {
+    let Some(entry) = self.entries.get_mut(self.layout.fields.len()).filter(|entry| entry.name == GOD_MODE) else {
+      return;
+    };
+    if entry.next_write.is_none_or(|next| next > now) {
+      return;
+    }
+
+    entry.next_write = Some(now + entry.interval);
+    match unit::heal_units(memory, &self.layout, self.player, entry.mode) {
+      Ok(written) => {
+        if written > 0 {
+          entry.stats.writes += 1;
+          entry.stats.last_write = Some(now);
+        }
+        if let FreezeMode::SetOnce(_) = entry.mode {
+          entry.mode = FreezeMode::Off;
+          entry.next_write = None;
+        }
+      }
+      // 游戏程序已运行，但是还未进入游戏状态，稍后重试
+      Err(TrainerError::NullPointer { .. }) => {}
+      Err(e) => {
+        entry.stats.failures += 1;
+        entry.stats.last_error = Some(e.to_string());
+      }
+    }
+  }
+
+  fn tick_toggles(&mut self, memory: &dyn ProcessMemory, now: Instant) {
+    let start = self.entries.len() - self.layout.toggles.len();
+    for (i, toggle) in self.layout.toggles.iter().enumerate() {
+      let entry = &mut self.entries[start + i];
+      let state = &mut self.toggle_states[i];
+
+      // 关闭之后恢复原来的值
+      if entry.mode == FreezeMode::Off {
+        if !state.saved.is_empty()
+          && let Err(e) = state.restore(memory) {
+          entry.stats.failures += 1;
+          entry.stats.last_error = Some(e.to_string());
+        }
+        continue;
+      }
+      if entry.next_write.is_none_or(|next| next > now) {
+        continue;
+      }
+
+      entry.next_write = Some(now + entry.interval);
+      match state.apply(memory, &self.layout, self.player, toggle, entry.mode) {
+        Ok(changed) => {
+          if changed {
+            entry.stats.writes += 1;
+            entry.stats.last_write = Some(now);
+          }
+          // 只写入一次，保持开启状态，关闭时仍然恢复
+          if let FreezeMode::SetOnce(_) = entry.mode {
+            entry.next_write = None;
+          }
+        }
+        Err(TrainerError::NullPointer { .. }) => {}
+        Err(e) => {
+          entry.stats.failures += 1;
+          entry.stats.last_error = Some(e.to_string());
+        }
+      }
+    }
+  }
+
+  /*
+    关闭所有修改，并把开关类修改写入的地址恢复为原来的值。停止锁定引擎之前调用
+  */
+  pub fn restore(&mut self, memory: &dyn ProcessMemory) -> Result<(), TrainerError> {
+    for entry in &mut self.entries {
+      entry.mode = FreezeMode::Off;
+      entry.next_write = None;
+    }
+    let mut result = Ok(());
+    for state in &mut self.toggle_states {
+      if let Err(e) = state.restore(memory) {
+        result = Err(e);
+      }
+    }
+    result
+  }
+}
+
+/*
+  锁定字段的后台任务，被 drop 时停止
+*/
+pub struct FreezeTask(tokio::task::JoinHandle<()>);
+
+impl Drop for FreezeTask {
+  fn drop(&mut self) {
+    self.0.abort();
+  }
+}
+
+/*
+  在后台不断写入锁定的字段，与界面的刷新无关
+*/
+pub fn spawn(engine: Arc<Mutex<FreezeEngine>>, memory: Arc<dyn ProcessMemory>) -> FreezeTask {
+  FreezeTask(tokio::spawn(async move {
+    loop {
+      let next = engine.lock().unwrap().tick(memory.as_ref(), Instant::now());
+      let wait = next.map_or(IDLE_INTERVAL, |next| next.saturating_duration_since(Instant::now()));
+      tokio::time::sleep(wait.min(IDLE_INTERVAL)).await;
+    }
+  }))
+}
+
+/*
+  开关类修改写入的一个地址：修改前的原始字节和最后一次写入的字节
+*/
+#[derive(Debug, Clone)]
+struct SavedBytes {
+  original: Vec<u8>,
+  written: Vec<u8>,
+}
+
+/*
+  开关类修改开启之后写入的地址。地址属于写入时的玩家结构体，换了一局游戏之后这些地址可能已经被游戏另作他用，
+  所以玩家结构体的指针变化或者变为空指针时，先恢复并清空记录
+*/
+#[derive(Debug, Clone, Default)]
+struct ToggleState {
+  // 写入时玩家结构体的地址，布局中没有玩家字段时为 None
+  owner: Option<usize>,
+  saved: HashMap<usize, SavedBytes>,
+}
+
+impl ToggleState {
+  /*
+    开启开关类修改：第一次写入某个地址前记录原来的值。返回是否有地址的值被改变
+  */
+  fn apply(
+    &mut self,
+    memory: &dyn ProcessMemory,
+    layout: &Layout,
+    player: Option<usize>,
+    toggle: &ToggleDef,
+    mode: FreezeMode,
+  ) -> Result<bool, TrainerError> {
+    let Some(scale) = mode.value() else {
+      return Ok(false);
+    };
+
+    let owner = match layout.player_base(player, memory.bitness().pointer_size()) {
+      None => None,
+      Some(chain) => match chain.resolve(memory).and_then(|addr| memory.read_pointer(addr)) {
+        // 游戏已经结束，恢复上一局写入的值
+        Ok(0) | Err(TrainerError::NullPointer { .. }) => {
+          self.restore(memory)?;
+          return Ok(false);
+        }
+        Ok(pointer) => Some(pointer),
+        Err(e) => return Err(e),
+      },
+    };
+    if self.owner != owner {
+      self.restore(memory)?;
+      self.owner = owner;
+    }
+
+    let mut changed = false;
+    for write in &toggle.writes {
+      for addr in toggle_addresses(memory, layout, player, toggle, &write.target)? {
+        let mut current = vec![0u8; write.value_type.size()];
+        memory.read_bytes(addr, &mut current)?;
+        // 游戏自己修改了这个值（例如研究了科技），以新的值作为原来的值
+        let saved = self.saved.entry(addr)
+          .and_modify(|saved| if saved.written != current { saved.original = current.clone() })
+          .or_insert_with(|| SavedBytes { original: current.clone(), written: current.clone() });
+        let value = match write.value {
+          Some(value) => value,
+          None => Value::from_bytes(write.value_type, &saved.original)?.as_f64() * scale,
+        };
+        let bytes = Value::from_f64(write.value_type, value).to_bytes();
+        if bytes != current {
+          memory.write_bytes(addr, &bytes)?;
+          changed = true;
+        }
+        saved.written = bytes;
+      }
+    }
+    Ok(changed)
+  }
+
+  /*
+    把记录的地址恢复为原来的值，然后清空记录。只恢复仍然是上次写入的值的地址，
+    已经无法读取或者被游戏改写的地址直接丢弃
+  */
+  fn restore(&mut self, memory: &dyn ProcessMemory) -> Result<(), TrainerError> {
+    let mut result = Ok(());
+    for (addr, saved) in self.saved.drain() {
+      let mut current = vec![0u8; saved.written.len()];
+      if memory.read_bytes(addr, &mut current).is_err() || current != saved.written {
+        continue;
+      }
+      if let Err(e) = memory.write_bytes(addr, &saved.original) {
+        result = Err(e);
+      }
+    }
+    self.owner = None;
+    result
+  }
+}
+
+/*
+  开关类修改中一次写入的所有地址。数组中为空指针的元素会被跳过
+*/
+fn toggle_addresses(
+  memory: &dyn ProcessMemory,
+  layout: &Layout,
+  player: Option<usize>,
+  toggle: &ToggleDef,
+  target: &ToggleTarget,
+) -> Result<Vec<usize>, TrainerError> {
+  let pointer_size = memory.bitness().pointer_size();
+  let player_chain = |offsets: &[usize]| {
+    layout.player_chain(player, pointer_size, offsets)
+      .ok_or_else(|| TrainerError::Layout(format!("toggle \"{}\" needs player fields", toggle.name)))
+  };
+
+  let (array, offsets) = match target {
+    ToggleTarget::Chain(chain) => return Ok(vec![chain.resolve(memory)?]),
+    ToggleTarget::Player(offsets) => return Ok(vec![player_chain(offsets)?.resolve(memory)?]),
+    ToggleTarget::Array { array, offsets } => (array, offsets),
+  };
+
+  let count_addr = player_chain(&array.count.offsets)?.resolve(memory)?;
+  let count = Value::read(memory, count_addr, array.count.value_type)?.as_f64();
+  if count <= 0.0 {
+    return Ok(vec![]);
+  }
+  if count > MAX_ARRAY_LEN as f64 {
+    return Err(TrainerError::Layout(format!("array length {} of toggle \"{}\" is out of range", count, toggle.name)));
+  }
+  let base = memory.read_pointer(player_chain(&array.list)?.resolve(memory)?)?;
+  if base == 0 {
+    return Ok(vec![]);
+  }
+
+  let mut addrs = vec![];
+  for i in 0..count as usize {
+    let element = base.wrapping_add(i * array.stride);
+    let chain = PointerChain { base: PointerBase::Absolute(element.wrapping_add(offsets[0])), offsets: offsets[1..].to_vec() };
+    match chain.resolve(memory) {
+      Ok(addr) => addrs.push(addr),
+      // 数组中没有使用的元素
+      Err(TrainerError::NullPointer { .. }) => {}
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(addrs)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  // 内置布局中保存本地玩家结构体指针的地址
+  const PLAYER_SLOT: usize = 0x400000 + 0x3A5FEC;
+  // 全图的开关写入 [玩家 +0x88]+0x2C
+  const MAP_STATE: usize = 0x88;
+  const REVEALED: usize = 0x2C;
+
+  /*
+    本地玩家结构体在 player，地图可见状态对象在 map_state
+  */
+  fn map_player(memory: &BufferMemory, player: usize, map_state: usize) {
+    let mut data = vec![0u8; 0x200];
+    data[MAP_STATE..MAP_STATE + 4].copy_from_slice(&(map_state as u32).to_le_bytes());
+    memory.map(player, data);
+    memory.map(map_state, vec![0u8; 0x40]);
+    memory.write(PLAYER_SLOT, player as u32).unwrap();
+  }
+
+  fn game() -> BufferMemory {
+    let memory = BufferMemory::new();
+    memory.map(0x400000, vec![0u8; 0x10]);
+    memory.add_module("age2_x1.exe", 0x400000);
+    memory.map(PLAYER_SLOT, vec![0u8; 4]);
+    map_player(&memory, 0x10000, 0x20000);
+    memory
+  }
+
+  fn reveal_map(layout: &Layout) -> &ToggleDef {
+    layout.toggles.iter().find(|toggle| toggle.name == "reveal_map").unwrap()
+  }
+
+  #[test]
+  fn restores_when_player_changes() {
+    let memory = game();
+    let layout = Layout::builtin();
+    let mut state = ToggleState::default();
+    assert!(state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap());
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 1);
+
+    // 新的一局游戏，玩家结构体换了地址
+    map_player(&memory, 0x30000, 0x40000);
+    assert!(state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap());
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 0);
+    assert_eq!(memory.read::<u8>(0x40000 + REVEALED).unwrap(), 1);
+    assert_eq!(state.owner, Some(0x30000));
+    assert_eq!(state.saved.len(), 1);
+  }
+
+  #[test]
+  fn restores_when_player_is_null() {
+    let memory = game();
+    let layout = Layout::builtin();
+    let mut state = ToggleState::default();
+    state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap();
+
+    // 游戏结束，玩家指针变为空指针
+    memory.write(PLAYER_SLOT, 0u32).unwrap();
+    assert!(!state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap());
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 0);
+    assert!(state.saved.is_empty());
+    assert_eq!(state.owner, None);
+  }
+
+  #[test]
+  fn adopts_value_changed_by_game() {
+    let memory = game();
+    let layout = Layout::builtin();
+    let mut state = ToggleState::default();
+    state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap();
+
+    // 游戏自己改了这个值，再次写入后关闭时恢复为游戏的值
+    memory.write(0x20000 + REVEALED, 2u8).unwrap();
+    assert!(state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap());
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 1);
+    state.restore(&memory).unwrap();
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 2);
+  }
+
+  #[test]
+  fn skips_restore_of_overwritten_bytes() {
+    let memory = game();
+    let layout = Layout::builtin();
+    let mut state = ToggleState::default();
+    state.apply(&memory, &layout, None, reveal_map(&layout), FreezeMode::Freeze(1.0)).unwrap();
+
+    // 游戏改写了写入的值之后才关闭，不能用原来的值覆盖游戏的值
+    memory.write(0x20000 + REVEALED, 5u8).unwrap();
+    state.restore(&memory).unwrap();
+    assert_eq!(memory.read::<u8>(0x20000 + REVEALED).unwrap(), 5);
+    assert!(state.saved.is_empty());
+  }
+
+  #[test]
+  fn scales_shared_type_once() {
+    let memory = game();
+    // 三项单位类型中前两项是同一个类型对象
+    let player = 0x10000;
+    memory.write(player + 0x70, 3i32).unwrap();
+    memory.write(player + 0x74, 0x50000u32).unwrap();
+    let mut types = vec![];
+    for pointer in [0x60000u32, 0x60000, 0x70000] {
+      types.extend_from_slice(&pointer.to_le_bytes());
+    }
+    memory.map(0x50000, types);
+    memory.map(0x60000, vec![0u8; 0x200]);
+    memory.map(0x70000, vec![0u8; 0x200]);
+    memory.write(0x60000 + 0x11C, 0.5f32).unwrap();
+    memory.write(0x70000 + 0x11C, 0.25f32).unwrap();
+
+    let mut engine = FreezeEngine::new(&Layout::builtin());
+    assert!(engine.set("gather_rate", FreezeMode::Freeze(2.0), DEFAULT_INTERVAL));
+    let now = Instant::now();
+    for i in 0..3 {
+      engine.tick(&memory, now + DEFAULT_INTERVAL * i);
+      assert_eq!(memory.read::<f32>(0x60000 + 0x11C).unwrap(), 1.0);
+      assert_eq!(memory.read::<f32>(0x70000 + 0x11C).unwrap(), 0.5);
+    }
+
+    engine.set("gather_rate", FreezeMode::Off, DEFAULT_INTERVAL);
+    engine.tick(&memory, now + DEFAULT_INTERVAL * 3);
+    assert_eq!(memory.read::<f32>(0x60000 + 0x11C).unwrap(), 0.5);
+    assert_eq!(memory.read::<f32>(0x70000 + 0x11C).unwrap(), 0.25);
+  }
+
+  #[test]
+  fn set_once_writes_field_once() {
+    let memory = game();
+    // 本地玩家的资源数组
+    memory.write(0x10000 + 0xA8, 0x80000u32).unwrap();
+    memory.map(0x80000, vec![0u8; 0x40]);
+
+    let mut engine = FreezeEngine::new(&Layout::builtin());
+    engine.set("food", FreezeMode::SetOnce(1000.0), DEFAULT_INTERVAL);
+    let now = Instant::now();
+    engine.tick(&memory, now);
+    assert_eq!(memory.read::<f32>(0x80000).unwrap(), 1000.0);
+    assert_eq!(engine.entry("food").unwrap().mode, FreezeMode::Off);
+    assert_eq!(engine.entry("food").unwrap().stats.writes, 1);
+
+    // 游戏消耗了食物之后不再写入
+    memory.write(0x80000, 200.0f32).unwrap();
+    engine.tick(&memory, now + DEFAULT_INTERVAL);
+    assert_eq!(memory.read::<f32>(0x80000).unwrap(), 200.0);
+  }
+}
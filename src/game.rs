@@ -0,0 +1,187 @@
+use crate::error::TrainerError;
+use crate::layout::{Layout, AttrDef, PlayerNameDef, PlayersDef};
+use crate::value::Value;
+use crate::winapi::memory::{ProcessMemory, ProcessMemoryExt};
+
+
+#[derive(Debug, Clone)]
+pub struct FieldValue {
+  pub name: String,
+  pub label: String,
+  pub value: Value,
+}
+
+/*
+  一个玩家的数据，index 是玩家在玩家数组中的下标，0 为盖亚
+*/
+#[derive(Debug, Clone)]
+pub struct PlayerInfo {
+  pub index: usize,
+  // 是否是本地玩家
+  pub is_local: bool,
+  // 以下信息在布局中没有填写或者读取失败时为 None
+  pub name: Option<String>,
+  pub civilization: Option<String>,
+  pub color: Option<String>,
+  pub team: Option<u32>,
+  pub is_human: Option<bool>,
+  pub fields: Vec<FieldValue>,
+}
+
+impl PlayerInfo {
+  fn new(index: usize, is_local: bool, fields: Vec<FieldValue>) -> Self {
+    Self { index, is_local, name: None, civilization: None, color: None, team: None, is_human: None, fields }
+  }
+
+  pub fn get(&self, name: &str) -> Option<Value> {
+    self.fields.iter().find(|field| field.name == name).map(|field| field.value)
+  }
+}
+
+/*
+  按内存布局读取到的游戏数据
+*/
+#[derive(Debug, Clone, Default)]
+pub struct GameInfo {
+  pub pid: u32,
+  pub is_running: bool,
+  // 不属于玩家的字段
+  pub fields: Vec<FieldValue>,
+  // 所有玩家的数据，布局中没有玩家数组时只有本地玩家
+  pub players: Vec<PlayerInfo>,
+}
+
+impl GameInfo {
+  /*
+    先查找本地玩家的字段，再查找不属于玩家的字段
+  */
+  pub fn get(&self, name: &str) -> Option<Value> {
+    self.local_player()
+      .and_then(|player| player.get(name))
+      .or_else(|| self.fields.iter().find(|field| field.name == name).map(|field| field.value))
+  }
+
+  pub fn player(&self, index: usize) -> Option<&PlayerInfo> {
+    self.players.iter().find(|player| player.index == index)
+  }
+
+  pub fn local_player(&self) -> Option<&PlayerInfo> {
+    self.players.iter().find(|player| player.is_local)
+  }
+}
+
+/*
+  按内存布局读取所有字段，同一块中的字段一次读取。
+  读到空指针时返回 None，表示游戏还未开始
+*/
+fn read_fields(memory: &dyn ProcessMemory, layout: &Layout) -> Result<Option<Vec<FieldValue>>, TrainerError> {
+  let mut values: Vec<Option<Value>> = vec![None; layout.fields.len()];
+  for block in layout.blocks() {
+    let data = match block.read(memory) {
+      Ok((_, data)) => data,
+      Err(TrainerError::NullPointer { .. }) => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    for (index, offset) in block.fields {
+      let value_type = layout.fields[index].value_type;
+      values[index] = Some(Value::from_bytes(value_type, &data[offset..offset + value_type.size()])?);
+    }
+  }
+
+  let fields = layout.fields.iter().zip(values)
+    .filter_map(|(field, value)| Some(FieldValue {
+      name: field.name.clone(),
+      label: field.label.clone(),
+      value: value?,
+    }))
+    .collect();
+  Ok(Some(fields))
+}
+
+/*
+  读取游戏数据：不属于玩家的字段，以及玩家数组中每个玩家的字段
+*/
+pub fn read_game_value(memory: &dyn ProcessMemory, layout: &Layout, info: &mut GameInfo) -> Result<(), TrainerError> {
+  // 游戏程序已运行，但是还未进入游戏状态
+  let Some(fields) = read_fields(memory, &layout.filter_fields(|field| !field.player))? else {
+    return Ok(());
+  };
+  let player_layout = layout.filter_fields(|field| field.player);
+  let Some(local) = read_fields(memory, &player_layout)? else {
+    return Ok(());
+  };
+  info.fields = fields;
+
+  if layout.players.is_none() {
+    if !local.is_empty() {
+      info.players.push(PlayerInfo::new(0, true, local));
+    }
+    info.is_running = true;
+    return Ok(());
+  }
+
+  let players = layout.players.as_ref().unwrap();
+  let pointer_size = memory.bitness().pointer_size();
+  let local_pointer = match layout.local_player_slot() {
+    Some(slot) => Some(memory.read_pointer(slot.resolve(memory)?)?),
+    None => None,
+  };
+  let mut index = 0;
+  while let Some(slot) = layout.player_slot(index, pointer_size) {
+    // 数组中没有玩家的位置为空指针；玩家数少于数组长度时，后面的元素可能读不到
+    let pointer = slot.resolve(memory).and_then(|addr| memory.read_pointer(addr));
+    if let Ok(pointer) = pointer
+      && pointer != 0
+      && let Ok(Some(fields)) = read_fields(memory, &player_layout.with_player(Some(index), pointer_size)) {
+      let mut player = PlayerInfo::new(index, Some(pointer) == local_pointer, fields);
+      read_player_meta(memory, layout, players, &mut player);
+      info.players.push(player);
+    }
+    index += 1;
+  }
+
+  info.is_running = true;
+  Ok(())
+}
+
+/*
+  读取玩家的名称、文明、颜色、队伍以及是否是人类玩家，读取失败的信息保留为 None
+*/
+fn read_player_meta(memory: &dyn ProcessMemory, layout: &Layout, players: &PlayersDef, player: &mut PlayerInfo) {
+  let pointer_size = memory.bitness().pointer_size();
+  let read_attr = |attr: &Option<AttrDef>| -> Option<f64> {
+    let attr = attr.as_ref()?;
+    let chain = layout.player_chain(Some(player.index), pointer_size, &attr.offsets)?;
+    let addr = chain.resolve(memory).ok()?;
+    Value::read(memory, addr, attr.value_type).ok().map(|value| value.as_f64())
+  };
+  let lookup = |names: &[String], id: f64| {
+    names.get(id as usize).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| id.to_string())
+  };
+
+  let civilization = read_attr(&players.civilization).map(|id| lookup(&players.civilizations, id));
+  let color = read_attr(&players.color).map(|id| lookup(&players.colors, id));
+  let team = read_attr(&players.team).map(|team| team as u32);
+  let is_human = read_attr(&players.human).map(|human| human != 0.0);
+  let name = players.name.as_ref().and_then(|name| read_player_name(memory, layout, player.index, name));
+
+  player.name = name;
+  player.civilization = civilization;
+  player.color = color;
+  player.team = team;
+  player.is_human = is_human;
+}
+
+/*
+  读取以 0 结尾的玩家名称并按布局中的编码解码
+*/
+fn read_player_name(memory: &dyn ProcessMemory, layout: &Layout, index: usize, def: &PlayerNameDef) -> Option<String> {
+  let chain = layout.player_chain(Some(index), memory.bitness().pointer_size(), &def.offsets)?;
+  let addr = chain.resolve(memory).ok()?;
+  let mut data = vec![0u8; def.length];
+  memory.read_bytes(addr, &mut data).ok()?;
+  let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+  let encoding = encoding_rs::Encoding::for_label(def.encoding.as_bytes())?;
+  let (name, _) = encoding.decode_without_bom_handling(&data[..end]);
+  Some(name.into_owned())
+}
@@ -0,0 +1,795 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::TrainerError;
+use crate::freeze::GOD_MODE;
+use crate::profile::Fingerprint;
+use crate::scan::Signature;
+use crate::value::{Value, ValueType};
+use crate::winapi::memory::ProcessMemory;
+use crate::winapi::pointer::{PointerBase, PointerChain};
+
+// 程序内置的内存布局
+const BUILTIN_LAYOUT: &str = include_str!("../layouts/age2_x1.toml");
+// 合并读取时一块内存的最大长度，相距太远的字段分开读取
+const MAX_BLOCK_SIZE: usize = 0x1000;
+// 玩家数组的最大长度
+const MAX_PLAYERS: usize = 16;
+
+
+/*
+  游戏内存布局：描述每个字段的指针链、数值类型以及界面上显示的名称。
+  带有识别条件时也作为一个游戏版本的配置使用，格式参考 layouts/age2_x1.toml
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layout {
+  pub name: String,
+  // 识别游戏版本的条件，作为版本配置使用时必须填写
+  pub detect: Option<DetectRule>,
+  // 用特征码定位的基址，字段中用 @name 引用
+  #[serde(default, rename = "signature")]
+  pub signatures: Vec<SignatureDef>,
+  // 所有玩家的结构体指针数组，没有填写时只能读写本地玩家
+  pub players: Option<PlayersDef>,
+  // 玩家的单位列表
+  pub units: Option<UnitsDef>,
+  // 开启时写入、关闭时恢复原来的值的修改，例如全图
+  #[serde(default, rename = "toggle")]
+  pub toggles: Vec<ToggleDef>,
+  #[serde(rename = "field")]
+  pub fields: Vec<FieldDef>,
+}
+
+/*
+  识别游戏版本的条件，只需要填写其中一部分；header_hash 为十六进制字符串
+*/
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectRule {
+  pub image_name: Option<String>,
+  pub image_size: Option<u32>,
+  pub timestamp: Option<u32>,
+  pub header_hash: Option<String>,
+}
+
+impl DetectRule {
+  /*
+    所有填写的条件都满足时返回满足的条件数，否则返回 None
+  */
+  pub fn score(&self, fingerprint: &Fingerprint) -> Option<usize> {
+    let mut score = 0;
+    if let Some(image_name) = &self.image_name {
+      if !image_name.eq_ignore_ascii_case(&fingerprint.image_name) {
+        return None;
+      }
+      score += 1;
+    }
+    if let Some(image_size) = self.image_size {
+      if image_size != fingerprint.image_size {
+        return None;
+      }
+      score += 1;
+    }
+    if let Some(timestamp) = self.timestamp {
+      if timestamp != fingerprint.timestamp {
+        return None;
+      }
+      score += 1;
+    }
+    if let Some(header_hash) = &self.header_hash {
+      if parse_hex_u64(header_hash) != Some(fingerprint.header_hash) {
+        return None;
+      }
+      score += 1;
+    }
+    Some(score)
+  }
+
+  fn is_empty(&self) -> bool {
+    self.image_name.is_none() && self.image_size.is_none() && self.timestamp.is_none() && self.header_hash.is_none()
+  }
+}
+
+/*
+  在模块的代码节中用特征码查找基址，字段的 base 写为 "@name" 或 "@name+0x10"
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignatureDef {
+  pub name: String,
+  pub module: String,
+  #[serde(flatten)]
+  pub signature: Signature,
+}
+
+/*
+  玩家数组：array 指向的地址中保存玩家指针数组的地址，数组中依次是每个玩家结构体的指针，第 0 个为盖亚。
+  标记为 player 的字段的 base 中保存的是本地玩家的指针，读写其它玩家时改为数组中的元素
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawPlayersDef")]
+pub struct PlayersDef {
+  pub array: PointerChain,
+  // 数组的最大长度，包括盖亚
+  pub count: usize,
+  // 以下是玩家结构体中的信息，offsets 与玩家字段相同，从玩家结构体的指针出发
+  pub name: Option<PlayerNameDef>,
+  pub civilization: Option<AttrDef>,
+  pub color: Option<AttrDef>,
+  pub team: Option<AttrDef>,
+  // 不为 0 时是人类玩家，否则是电脑
+  pub human: Option<AttrDef>,
+  // 按编号排列的文明名称和颜色名称，没有名称的编号直接显示数字
+  pub civilizations: Vec<String>,
+  pub colors: Vec<String>,
+}
+
+/*
+  玩家或者单位结构体中的数值，offsets 从结构体的指针出发：先读取指针，再加上偏移
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttrDef {
+  #[serde(rename = "type")]
+  pub value_type: ValueType,
+  pub offsets: Vec<usize>,
+}
+
+/*
+  玩家名称：以 0 结尾的字符串，中文版游戏使用 GBK 编码
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerNameDef {
+  pub offsets: Vec<usize>,
+  // 最多读取的字节数
+  #[serde(default = "default_name_length")]
+  pub length: usize,
+  // 字符串的编码，使用 WHATWG 的编码名称，例如 gbk、windows-1252
+  #[serde(default = "default_name_encoding")]
+  pub encoding: String,
+}
+
+/*
+  玩家的单位列表：list 从玩家结构体的指针出发，指向的地址中保存单位指针数组的地址，count 为数组的长度。
+  其它数值从单位结构体的指针出发
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnitsDef {
+  pub list: Vec<usize>,
+  pub count: AttrDef,
+  pub id: AttrDef,
+  // 单位类型编号
+  pub type_id: AttrDef,
+  // 所属玩家的编号，没有填写时为单位列表所在的玩家
+  pub owner: Option<AttrDef>,
+  pub hit_points: AttrDef,
+  pub x: AttrDef,
+  pub y: AttrDef,
+  // 当前行动的编号，没有行动时指针为空
+  pub action: Option<AttrDef>,
+  // 单位的最大生命值，无敌模式使用
+  pub max_hit_points: Option<AttrDef>,
+  // 可以修改的单位类型属性，同一玩家同一类型的单位共用
+  #[serde(default, rename = "attribute")]
+  pub attributes: Vec<UnitAttrDef>,
+}
+
+/*
+  单位类型的属性，例如攻击、护甲、速度、视野。offsets 从单位结构体的指针出发
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnitAttrDef {
+  pub name: String,
+  pub label: String,
+  #[serde(rename = "type")]
+  pub value_type: ValueType,
+  pub offsets: Vec<usize>,
+}
+
+impl UnitAttrDef {
+  pub fn attr(&self) -> AttrDef {
+    AttrDef { value_type: self.value_type, offsets: self.offsets.clone() }
+  }
+}
+
+impl UnitsDef {
+  pub fn attribute(&self, name: &str) -> Option<&UnitAttrDef> {
+    self.attributes.iter().find(|attr| attr.name == name)
+  }
+}
+
+/*
+  开关类的修改：开启时记录原来的值并按写入间隔不断写入，关闭时恢复原来的值
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToggleDef {
+  pub name: String,
+  pub label: String,
+  #[serde(rename = "write")]
+  pub writes: Vec<ToggleWrite>,
+}
+
+impl ToggleDef {
+  /*
+    是否有没有固定写入值的写入，此时修改配置中的数值是倍数
+  */
+  pub fn scaled(&self) -> bool {
+    self.writes.iter().any(|write| write.value.is_none())
+  }
+}
+
+/*
+  开关类修改中的一次写入：地址为 chain（Cheat Engine 写法的指针链），或者从修改的玩家结构体的指针出发的 offsets。
+  填写 each 时写入玩家的某个数组中的每个元素，offsets 从元素的地址出发。
+  填写 value 时写入该值，否则写入 原来的值 × 修改配置中的数值
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawToggleWrite")]
+pub struct ToggleWrite {
+  pub target: ToggleTarget,
+  pub value_type: ValueType,
+  pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ToggleTarget {
+  Chain(PointerChain),
+  Player(Vec<usize>),
+  // 第一个偏移直接加在元素的地址上，之后每一级先读取指针再加上偏移
+  Array { array: ArrayDef, offsets: Vec<usize> },
+}
+
+/*
+  玩家结构体中的数组：list 从玩家结构体的指针出发，指向的地址中保存数组的地址；
+  count 为数组的长度，stride 为每个元素的字节数
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArrayDef {
+  pub list: Vec<usize>,
+  pub count: AttrDef,
+  pub stride: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawToggleWrite {
+  chain: Option<String>,
+  offsets: Option<Vec<usize>>,
+  each: Option<ArrayDef>,
+  #[serde(rename = "type")]
+  value_type: ValueType,
+  value: Option<f64>,
+}
+
+impl TryFrom<RawToggleWrite> for ToggleWrite {
+  type Error = String;
+
+  fn try_from(raw: RawToggleWrite) -> Result<Self, Self::Error> {
+    let target = match (raw.chain, raw.offsets, raw.each) {
+      (Some(chain), None, None) => ToggleTarget::Chain(chain.parse::<PointerChain>().map_err(|e| match e {
+        TrainerError::Layout(msg) => msg,
+        e => e.to_string(),
+      })?),
+      (None, Some(offsets), None) if !offsets.is_empty() => ToggleTarget::Player(offsets),
+      (None, Some(offsets), Some(array)) if !offsets.is_empty() => {
+        if array.list.is_empty() || array.count.offsets.is_empty() || array.stride == 0 {
+          return Err("each must have non-empty list and count offsets and a non-zero stride".to_string());
+        }
+        ToggleTarget::Array { array, offsets }
+      }
+      _ => return Err("toggle write must have either chain or non-empty offsets".to_string()),
+    };
+    Ok(Self { target, value_type: raw.value_type, value: raw.value })
+  }
+}
+
+fn default_name_length() -> usize {
+  64
+}
+
+fn default_name_encoding() -> String {
+  "gbk".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPlayersDef {
+  array: String,
+  count: usize,
+  name: Option<PlayerNameDef>,
+  civilization: Option<AttrDef>,
+  color: Option<AttrDef>,
+  team: Option<AttrDef>,
+  human: Option<AttrDef>,
+  #[serde(default)]
+  civilizations: Vec<String>,
+  #[serde(default)]
+  colors: Vec<String>,
+}
+
+impl TryFrom<RawPlayersDef> for PlayersDef {
+  type Error = String;
+
+  fn try_from(raw: RawPlayersDef) -> Result<Self, Self::Error> {
+    let array = raw.array.parse::<PointerChain>().map_err(|e| match e {
+      TrainerError::Layout(msg) => msg,
+      e => e.to_string(),
+    })?;
+    Ok(Self {
+      array,
+      count: raw.count,
+      name: raw.name,
+      civilization: raw.civilization,
+      color: raw.color,
+      team: raw.team,
+      human: raw.human,
+      civilizations: raw.civilizations,
+      colors: raw.colors,
+    })
+  }
+}
+
+fn parse_hex_u64(text: &str) -> Option<u64> {
+  let text = text.trim();
+  let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+  u64::from_str_radix(digits, 16).ok()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawFieldDef")]
+pub struct FieldDef {
+  pub name: String,
+  pub label: String,
+  pub value_type: ValueType,
+  pub chain: PointerChain,
+  // 是否是玩家结构体中的字段
+  pub player: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFieldDef {
+  name: String,
+  label: String,
+  #[serde(rename = "type")]
+  value_type: ValueType,
+  module: Option<String>,
+  base: RawBase,
+  #[serde(default)]
+  offsets: Vec<usize>,
+  #[serde(default)]
+  player: bool,
+}
+
+/*
+  base 可以是地址，也可以是 "age2_x1.exe"+0x3A5FEC 这样相对于模块的写法
+*/
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBase {
+  Address(usize),
+  Expr(String),
+}
+
+impl TryFrom<RawFieldDef> for FieldDef {
+  type Error = String;
+
+  fn try_from(raw: RawFieldDef) -> Result<Self, Self::Error> {
+    let base = match (raw.module, raw.base) {
+      (Some(name), RawBase::Address(offset)) => PointerBase::Module { name, offset },
+      (None, RawBase::Address(addr)) => PointerBase::Absolute(addr),
+      (None, RawBase::Expr(expr)) => expr.parse::<PointerBase>().map_err(|e| match e {
+        TrainerError::Layout(msg) => msg,
+        e => e.to_string(),
+      })?,
+      (Some(_), RawBase::Expr(_)) => {
+        return Err(format!("field \"{}\": module must not be set when base is an expression", raw.name));
+      }
+    };
+    Ok(Self {
+      name: raw.name,
+      label: raw.label,
+      value_type: raw.value_type,
+      chain: PointerChain { base, offsets: raw.offsets },
+      player: raw.player,
+    })
+  }
+}
+
+impl FieldDef {
+  pub fn read(&self, memory: &dyn ProcessMemory) -> Result<Value, TrainerError> {
+    let addr = self.chain.resolve(memory)?;
+    Value::read(memory, addr, self.value_type)
+  }
+
+  /*
+    写入数值，数值会先转换为字段的类型
+  */
+  pub fn write(&self, memory: &dyn ProcessMemory, value: f64) -> Result<(), TrainerError> {
+    let addr = self.chain.resolve(memory)?;
+    Value::from_f64(self.value_type, value).write(memory, addr)
+  }
+}
+
+/*
+  指针链只有最后一级偏移不同的一组字段，例如同一个玩家资源数组中的字段。
+  同一块中的字段只需要解析一次指针链，并用一次调用读取整块内存，得到的数据也不会被游戏写到一半
+*/
+#[derive(Debug, Clone)]
+pub struct FieldBlock {
+  // 指向块起始地址的指针链
+  pub chain: PointerChain,
+  pub size: usize,
+  // (字段在 Layout::fields 中的下标, 字段在块中的偏移)
+  pub fields: Vec<(usize, usize)>,
+  // 每个字段在块中占用的字节范围，按偏移排序
+  spans: Vec<Range<usize>>,
+}
+
+impl FieldBlock {
+  /*
+    读取整块内存，返回块的起始地址和数据
+  */
+  pub fn read(&self, memory: &dyn ProcessMemory) -> Result<(usize, Vec<u8>), TrainerError> {
+    let addr = self.chain.resolve(memory)?;
+    let mut data = vec![0u8; self.size];
+    memory.read_bytes(addr, &mut data)?;
+    Ok((addr, data))
+  }
+
+  /*
+    只把 old 和 new 中改变的字段写回：改变的字段整个写入，相邻的改变字段合并为一次写入。
+    没有改变的字段可能在读取之后被游戏修改，不能用读取时的数据覆盖
+  */
+  pub fn write_changes(&self, memory: &dyn ProcessMemory, addr: usize, old: &[u8], new: &[u8]) -> Result<(), TrainerError> {
+    let mut pending: Option<Range<usize>> = None;
+    for span in self.spans.iter().filter(|span| old[(*span).clone()] != new[(*span).clone()]) {
+      match &mut pending {
+        Some(run) if span.start <= run.end => run.end = run.end.max(span.end),
+        _ => {
+          if let Some(run) = pending.replace(span.clone()) {
+            memory.write_bytes(addr + run.start, &new[run])?;
+          }
+        }
+      }
+    }
+    if let Some(run) = pending {
+      memory.write_bytes(addr + run.start, &new[run])?;
+    }
+    Ok(())
+  }
+
+  fn new(chain: PointerChain, index: usize, size: usize) -> Self {
+    Self { chain, size, fields: vec![(index, 0)], spans: std::iter::once(0..size).collect() }
+  }
+}
+
+impl Layout {
+  pub fn builtin() -> Self {
+    Self::from_toml(BUILTIN_LAYOUT).expect("builtin layout is invalid")
+  }
+
+  pub fn from_toml(text: &str) -> Result<Self, TrainerError> {
+    let layout: Layout = toml::from_str(text).map_err(|e| TrainerError::Layout(e.to_string()))?;
+    layout.validate()?;
+    Ok(layout)
+  }
+
+  pub fn load(path: &Path) -> Result<Self, TrainerError> {
+    let text = std::fs::read_to_string(path)?;
+    Self::from_toml(&text).map_err(|e| match e {
+      TrainerError::Layout(msg) => TrainerError::Layout(format!("{}: {}", path.display(), msg)),
+      e => e,
+    })
+  }
+
+  /*
+    检查布局是否有效：至少有一个字段，字段名不能为空且不能重复，显示名称不能为空，识别条件至少填写一项
+  */
+  pub fn validate(&self) -> Result<(), TrainerError> {
+    if self.fields.is_empty() {
+      return Err(TrainerError::Layout(format!("layout \"{}\" has no fields", self.name)));
+    }
+    if let Some(detect) = &self.detect {
+      if detect.is_empty() {
+        return Err(TrainerError::Layout(format!("layout \"{}\" has an empty [detect] section", self.name)));
+      }
+      if let Some(header_hash) = &detect.header_hash
+        && parse_hex_u64(header_hash).is_none() {
+        return Err(TrainerError::Layout(format!("invalid header_hash \"{}\"", header_hash)));
+      }
+    }
+
+    let mut signatures: HashSet<&str> = HashSet::new();
+    for signature in &self.signatures {
+      if signature.name.is_empty() || signature.module.is_empty() {
+        return Err(TrainerError::Layout("signature name and module must not be empty".to_string()));
+      }
+      if !signatures.insert(&signature.name) {
+        return Err(TrainerError::Layout(format!("duplicate signature \"{}\"", signature.name)));
+      }
+      // 这里还不知道游戏进程的位数，64 位的绝对地址在查找时按指针大小检查
+      if signature.signature.operand + signature.signature.operand_size(4) > signature.signature.pattern.len() {
+        return Err(TrainerError::Layout(format!("operand of signature \"{}\" is out of the pattern", signature.name)));
+      }
+    }
+
+    if let Some(players) = &self.players {
+      if players.count == 0 || players.count > MAX_PLAYERS {
+        return Err(TrainerError::Layout(format!("player count must be between 1 and {}", MAX_PLAYERS)));
+      }
+      if let PointerBase::Signature { name, .. } = &players.array.base
+        && !signatures.contains(name.as_str()) {
+        return Err(TrainerError::Layout(format!("player array uses unknown signature \"{}\"", name)));
+      }
+      if let Some(name) = &players.name {
+        if encoding_rs::Encoding::for_label(name.encoding.as_bytes()).is_none() {
+          return Err(TrainerError::Layout(format!("unknown player name encoding \"{}\"", name.encoding)));
+        }
+        if name.length == 0 {
+          return Err(TrainerError::Layout("player name length must not be 0".to_string()));
+        }
+      }
+      if !self.fields.iter().any(|field| field.player) {
+        return Err(TrainerError::Layout("[players] is set but no field has player = true".to_string()));
+      }
+    }
+
+    if let Some(units) = &self.units {
+      let attrs = [
+        Some(&units.count), Some(&units.id), Some(&units.type_id), units.owner.as_ref(), Some(&units.hit_points),
+        Some(&units.x), Some(&units.y), units.action.as_ref(), units.max_hit_points.as_ref(),
+      ];
+      if units.list.is_empty()
+        || attrs.iter().flatten().any(|attr| attr.offsets.is_empty())
+        || units.attributes.iter().any(|attr| attr.offsets.is_empty()) {
+        return Err(TrainerError::Layout("offsets in [units] must not be empty".to_string()));
+      }
+      let mut attributes: HashSet<&str> = HashSet::new();
+      for attr in &units.attributes {
+        if attr.name.is_empty() || attr.label.is_empty() {
+          return Err(TrainerError::Layout("unit attribute name and label must not be empty".to_string()));
+        }
+        // hit_points 是每个单位自己的生命值，不能作为类型属性
+        if attr.name == "hit_points" || !attributes.insert(&attr.name) {
+          return Err(TrainerError::Layout(format!("duplicate unit attribute \"{}\"", attr.name)));
+        }
+      }
+    }
+
+    let mut toggles: HashSet<&str> = HashSet::new();
+    for toggle in &self.toggles {
+      if toggle.name.is_empty() || toggle.label.is_empty() {
+        return Err(TrainerError::Layout("toggle name and label must not be empty".to_string()));
+      }
+      if toggle.name == GOD_MODE || self.fields.iter().any(|field| field.name == toggle.name) || !toggles.insert(&toggle.name) {
+        return Err(TrainerError::Layout(format!("duplicate toggle \"{}\"", toggle.name)));
+      }
+      if toggle.writes.is_empty() {
+        return Err(TrainerError::Layout(format!("toggle \"{}\" has no writes", toggle.name)));
+      }
+      for write in &toggle.writes {
+        if let ToggleTarget::Chain(PointerChain { base: PointerBase::Signature { name, .. }, .. }) = &write.target
+          && !signatures.contains(name.as_str()) {
+          return Err(TrainerError::Layout(format!("toggle \"{}\" uses unknown signature \"{}\"", toggle.name, name)));
+        }
+      }
+    }
+
+    let mut names: HashSet<&str> = HashSet::new();
+    for field in &self.fields {
+      if field.name.is_empty() {
+        return Err(TrainerError::Layout("field name must not be empty".to_string()));
+      }
+      if field.label.is_empty() {
+        return Err(TrainerError::Layout(format!("field \"{}\" has an empty label", field.name)));
+      }
+      if !names.insert(&field.name) {
+        return Err(TrainerError::Layout(format!("duplicate field \"{}\"", field.name)));
+      }
+      if let PointerBase::Module { name, .. } = &field.chain.base
+        && name.is_empty() {
+        return Err(TrainerError::Layout(format!("field \"{}\" has an empty module name", field.name)));
+      }
+      if let PointerBase::Signature { name, .. } = &field.chain.base
+        && !signatures.contains(name.as_str()) {
+        return Err(TrainerError::Layout(format!("field \"{}\" uses unknown signature \"{}\"", field.name, name)));
+      }
+    }
+
+    Ok(())
+  }
+
+  /*
+    连接进程时查找所有特征码，把字段中的 @name 替换为找到的绝对地址
+  */
+  pub fn resolve_signatures(&mut self, memory: &dyn ProcessMemory) -> Result<(), TrainerError> {
+    let mut addrs: HashMap<&str, usize> = HashMap::new();
+    for signature in &self.signatures {
+      let addr = signature.signature.find(memory, &signature.module).map_err(|e| match e {
+        TrainerError::Signature(msg) => TrainerError::Signature(format!("{}: {}", signature.name, msg)),
+        e => e,
+      })?;
+      addrs.insert(&signature.name, addr);
+    }
+
+    let toggle_chains = self.toggles.iter_mut()
+      .flat_map(|toggle| &mut toggle.writes)
+      .filter_map(|write| match &mut write.target {
+        ToggleTarget::Chain(chain) => Some(chain),
+        ToggleTarget::Player(_) | ToggleTarget::Array { .. } => None,
+      });
+    let chains = self.fields.iter_mut()
+      .map(|field| &mut field.chain)
+      .chain(self.players.as_mut().map(|players| &mut players.array))
+      .chain(toggle_chains);
+    for chain in chains {
+      if let PointerBase::Signature { name, offset } = &chain.base {
+        let addr = addrs[name.as_str()];
+        chain.base = PointerBase::Absolute(addr.wrapping_add(*offset));
+      }
+    }
+    Ok(())
+  }
+
+  /*
+    只保留满足条件的字段
+  */
+  pub fn filter_fields(&self, f: impl Fn(&FieldDef) -> bool) -> Layout {
+    let mut layout = self.clone();
+    layout.fields.retain(f);
+    layout
+  }
+
+  /*
+    保存第 index 个玩家结构体指针的地址，没有玩家数组或者超出数组长度时返回 None
+  */
+  pub fn player_slot(&self, index: usize, pointer_size: usize) -> Option<PointerChain> {
+    let players = self.players.as_ref().filter(|players| index < players.count)?;
+    let mut chain = players.array.clone();
+    chain.offsets.push(index * pointer_size);
+    Some(chain)
+  }
+
+  /*
+    从第 index 个玩家结构体的指针出发、经过 offsets 的指针链，用于读取玩家结构体中的信息。
+    index 为 None 时为本地玩家
+  */
+  pub fn player_chain(&self, index: Option<usize>, pointer_size: usize, offsets: &[usize]) -> Option<PointerChain> {
+    let mut chain = self.player_base(index, pointer_size)?;
+    chain.offsets.extend_from_slice(offsets);
+    Some(chain)
+  }
+
+  /*
+    保存玩家结构体指针的地址，index 为 None 时为本地玩家
+  */
+  pub fn player_base(&self, index: Option<usize>, pointer_size: usize) -> Option<PointerChain> {
+    match index {
+      Some(index) => self.player_slot(index, pointer_size),
+      None => self.local_player_slot(),
+    }
+  }
+
+  /*
+    保存本地玩家结构体指针的地址，即第一个玩家字段的 base
+  */
+  pub fn local_player_slot(&self) -> Option<PointerChain> {
+    let field = self.fields.iter().find(|field| field.player)?;
+    Some(PointerChain { base: field.chain.base.clone(), offsets: vec![] })
+  }
+
+  /*
+    把玩家字段改为读写第 index 个玩家。index 为 None 时读写本地玩家，布局不变
+  */
+  pub fn with_player(&self, index: Option<usize>, pointer_size: usize) -> Layout {
+    let mut layout = self.clone();
+    let Some(slot) = index.and_then(|index| self.player_slot(index, pointer_size)) else {
+      return layout;
+    };
+    for field in layout.fields.iter_mut().filter(|field| field.player) {
+      let offsets = slot.offsets.iter().chain(&field.chain.offsets).copied().collect();
+      field.chain = PointerChain { base: slot.base.clone(), offsets };
+    }
+    layout
+  }
+
+  pub fn toggle(&self, name: &str) -> Option<&ToggleDef> {
+    self.toggles.iter().find(|toggle| toggle.name == name)
+  }
+
+  pub fn field(&self, name: &str) -> Option<&FieldDef> {
+    self.fields.iter().find(|field| field.name == name)
+  }
+
+  /*
+    把字段按指针链分组，用于合并读写。没有偏移的字段（绝对地址）单独成块
+  */
+  pub fn blocks(&self) -> Vec<FieldBlock> {
+    let mut fields: Vec<(usize, &FieldDef)> = self.fields.iter().enumerate().collect();
+    fields.sort_by_key(|(_, field)| field.chain.offsets.last().copied());
+
+    let mut blocks: Vec<FieldBlock> = vec![];
+    for (index, field) in fields {
+      let size = field.value_type.size();
+      let Some((&last, prefix)) = field.chain.offsets.split_last() else {
+        blocks.push(FieldBlock::new(field.chain.clone(), index, size));
+        continue;
+      };
+
+      let found = blocks.iter_mut().find(|block| {
+        let Some((&start, block_prefix)) = block.chain.offsets.split_last() else {
+          return false;
+        };
+        block.chain.base == field.chain.base
+          && block_prefix == prefix
+          && last >= start
+          && last - start + size <= MAX_BLOCK_SIZE
+      });
+      match found {
+        Some(block) => {
+          let offset = last - block.chain.offsets.last().unwrap();
+          block.size = block.size.max(offset + size);
+          block.fields.push((index, offset));
+          block.spans.push(offset..offset + size);
+          block.spans.sort_by_key(|span| span.start);
+        }
+        None => blocks.push(FieldBlock::new(field.chain.clone(), index, size)),
+      }
+    }
+    blocks
+  }
+}
+
+impl Default for Layout {
+  fn default() -> Self {
+    Self::builtin()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  #[test]
+  fn writes_only_changed_fields() {
+    let layout = Layout::builtin();
+    let block = layout.blocks().into_iter()
+      .find(|block| block.fields.len() > 1)
+      .unwrap();
+    let food = block.fields.iter().find(|(index, _)| layout.fields[*index].name == "food").unwrap().1;
+    let wood = block.fields.iter().find(|(index, _)| layout.fields[*index].name == "wood").unwrap().1;
+    let stone = block.fields.iter().find(|(index, _)| layout.fields[*index].name == "stone").unwrap().1;
+
+    let memory = BufferMemory::new();
+    let mut old = vec![0u8; block.size];
+    old[food..food + 4].copy_from_slice(&100.0f32.to_le_bytes());
+    memory.map(0x10000, old.clone());
+
+    let mut new = old.clone();
+    new[food..food + 4].copy_from_slice(&101.0f32.to_le_bytes());
+    new[stone..stone + 4].copy_from_slice(&500.0f32.to_le_bytes());
+    // 读取之后游戏修改了木材，以及食物的一部分字节
+    memory.write_bytes(0x10000 + wood, &42.0f32.to_le_bytes()).unwrap();
+    memory.write_bytes(0x10000 + food, &[0xFF]).unwrap();
+
+    block.write_changes(&memory, 0x10000, &old, &new).unwrap();
+    let read = |offset: usize| {
+      let mut bytes = [0u8; 4];
+      memory.read_bytes(0x10000 + offset, &mut bytes).unwrap();
+      f32::from_le_bytes(bytes)
+    };
+    assert_eq!(read(food), 101.0);
+    assert_eq!(read(wood), 42.0);
+    assert_eq!(read(stone), 500.0);
+  }
+}
@@ -1 +1,29 @@
-pub mod winapi;
\ No newline at end of file
+pub mod error;
+pub mod config;
+pub mod winapi;
+pub mod value;
+pub mod layout;
+pub mod profile;
+pub mod game;
+pub mod cheat;
+pub mod freeze;
+pub mod scan;
+pub mod unit;
+pub mod worker;
+
+pub use config::{CheatDef, CheatMode, Config};
+pub use error::TrainerError;
+pub use freeze::{FreezeEngine, FreezeEntry, FreezeMode, FreezeTask, GOD_MODE, WriteStats};
+pub use game::{FieldValue, GameInfo, PlayerInfo};
+pub use layout::{ArrayDef, AttrDef, DetectRule, FieldBlock, FieldDef, Layout, PlayerNameDef, PlayersDef, SignatureDef, ToggleDef, ToggleTarget, ToggleWrite, UnitAttrDef, UnitsDef};
+pub use profile::{Fingerprint, Profiles};
+pub use scan::{Pattern, PointerScanner, ScanFilter, Signature, ValueScanner};
+pub use unit::{UnitEditor, UnitInfo, UnitSelector};
+pub use value::{Value, ValueType};
+pub use winapi::WinApi;
+pub use worker::{GameSnapshot, GameStatus, GameWorker, WorkerCommand};
+pub use winapi::memory::{Bitness, BufferMemory, Kernel32Memory, MemoryRegion, ModuleInfo, Pod, ProcessMemory, ProcessMemoryExt, Protection, RegionState};
+#[cfg(target_os = "linux")]
+pub use winapi::linux::LinuxMemory;
+pub use winapi::pointer::{PointerBase, PointerChain};
+pub use winapi::process::{ProcessItem, ProcessManager};
@@ -1,16 +1,14 @@
 mod app;
-mod winapi;
 
 use color_eyre::Result;
-use tokio;
-use crossterm::event::{self, Event};
-use ratatui::{DefaultTerminal, Frame};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
+    // 在进入终端界面之前初始化，布局文件有错误时可以直接显示出来
+    let app = app::App::new()?;
     let terminal = ratatui::init();
-    let result = app::App::new().run(terminal).await;
+    let result = app.run(terminal).await;
     ratatui::restore();
     result
-}
\ No newline at end of file
+}
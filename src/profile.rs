@@ -0,0 +1,271 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::TrainerError;
+use crate::layout::Layout;
+use crate::winapi::memory::{Bitness, ProcessMemory};
+
+// 程序内置的版本配置
+const BUILTIN_PROFILES: [&str; 1] = [
+  include_str!("../layouts/age2_x1.toml"),
+];
+// 启动时在当前目录和程序所在目录中查找的配置目录，目录中的每个 .toml 文件都是一个版本配置
+const PROFILE_DIR_NAME: &str = "profiles";
+// 强制使用的内存布局，存在时不再自动识别游戏版本
+const LAYOUT_FILE_NAME: &str = "layout.toml";
+// 32 位 Windows 程序默认的加载基址
+const DEFAULT_IMAGE_BASE: usize = 0x00400000;
+// 计算哈希时读取的 PE 头长度
+const HEADER_SIZE: usize = 0x400;
+
+
+/*
+  游戏执行文件的特征，用于识别游戏版本
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+  pub image_name: String,
+  // PE 头中的 SizeOfImage
+  pub image_size: u32,
+  // PE 头中的 TimeDateStamp
+  pub timestamp: u32,
+  // PE 头前 0x400 字节的 FNV-1a 哈希
+  pub header_hash: u64,
+}
+
+impl fmt::Display for Fingerprint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "image_name = \"{}\", image_size = 0x{:X}, timestamp = 0x{:08X}, header_hash = \"0x{:016X}\"",
+      self.image_name, self.image_size, self.timestamp, self.header_hash
+    )
+  }
+}
+
+/*
+  从内存中读取执行文件的 PE 头，返回 PE 头的前 HEADER_SIZE 字节和 PE 签名的偏移
+*/
+fn read_pe_header(memory: &dyn ProcessMemory, image_name: &str) -> Result<(Vec<u8>, usize), TrainerError> {
+  let invalid = || TrainerError::UnknownVersion(format!("{}: invalid PE header", image_name));
+
+  let base = memory.module_base(image_name).unwrap_or(DEFAULT_IMAGE_BASE);
+  let mut header = vec![0u8; HEADER_SIZE];
+  memory.read_bytes(base, &mut header)?;
+
+  if &header[..2] != b"MZ" {
+    return Err(invalid());
+  }
+  let pe_offset = u32::from_le_bytes(header[0x3C..0x40].try_into().unwrap()) as usize;
+  // PE 签名(4) + 文件头(20) + 可选头中 SizeOfImage 之前的部分(56) + SizeOfImage(4)
+  if pe_offset + 84 > HEADER_SIZE || &header[pe_offset..pe_offset + 4] != b"PE\0\0" {
+    return Err(invalid());
+  }
+  Ok((header, pe_offset))
+}
+
+/*
+  从内存中读取执行文件的 PE 头，计算版本特征
+*/
+pub fn read_fingerprint(memory: &dyn ProcessMemory, image_name: &str) -> Result<Fingerprint, TrainerError> {
+  let (header, pe_offset) = read_pe_header(memory, image_name)?;
+  let read_u32 = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+  Ok(Fingerprint {
+    image_name: image_name.to_string(),
+    image_size: read_u32(pe_offset + 80),
+    timestamp: read_u32(pe_offset + 8),
+    header_hash: fnv1a64(&header),
+  })
+}
+
+/*
+  根据执行文件 PE 头判断进程位数：可选头的 Magic 为 0x10B 时是 32 位，0x20B 时是 64 位，
+  无法识别时再看文件头的 Machine（0x14C 为 x86，0x8664 为 x64）
+*/
+pub fn read_pe_bitness(memory: &dyn ProcessMemory, image_name: &str) -> Result<Bitness, TrainerError> {
+  let (header, pe_offset) = read_pe_header(memory, image_name)?;
+  let read_u16 = |offset: usize| u16::from_le_bytes(header[offset..offset + 2].try_into().unwrap());
+  match (read_u16(pe_offset + 24), read_u16(pe_offset + 4)) {
+    (0x10B, _) | (_, 0x14C) => Ok(Bitness::Bits32),
+    (0x20B, _) | (_, 0x8664) => Ok(Bitness::Bits64),
+    _ => Err(TrainerError::UnknownVersion(format!("{}: unknown PE machine type", image_name))),
+  }
+}
+
+fn fnv1a64(data: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for &byte in data {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
+
+
+/*
+  所有的游戏版本配置
+*/
+#[derive(Debug, Clone)]
+pub struct Profiles {
+  // layout.toml，存在时总是使用它
+  pub forced: Option<Layout>,
+  pub profiles: Vec<Layout>,
+}
+
+impl Profiles {
+  pub fn builtin() -> Self {
+    let profiles = BUILTIN_PROFILES.iter()
+      .map(|text| Layout::from_toml(text).expect("builtin profile is invalid"))
+      .collect();
+    Self { forced: None, profiles }
+  }
+
+  /*
+    启动时加载配置：内置配置，加上当前目录和程序所在目录中 profiles 目录下的 .toml 文件和 layout.toml
+  */
+  pub fn load_default() -> Result<Self, TrainerError> {
+    let mut profiles = Self::builtin();
+
+    for dir in search_dirs() {
+      let layout_file = dir.join(LAYOUT_FILE_NAME);
+      if profiles.forced.is_none() && layout_file.is_file() {
+        profiles.forced = Some(Layout::load(&layout_file)?);
+      }
+
+      let Ok(entries) = std::fs::read_dir(dir.join(PROFILE_DIR_NAME)) else {
+        continue;
+      };
+      let mut files: Vec<PathBuf> = entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+      files.sort();
+      for file in files {
+        let layout = Layout::load(&file)?;
+        if layout.detect.is_none() {
+          return Err(TrainerError::Layout(format!("{}: missing [detect] section", file.display())));
+        }
+        profiles.profiles.push(layout);
+      }
+    }
+
+    Ok(profiles)
+  }
+
+  /*
+    选择与执行文件特征最匹配的配置：所有填写的条件都必须满足，条件越多优先级越高
+  */
+  pub fn detect(&self, fingerprint: &Fingerprint) -> Option<&Layout> {
+    if let Some(layout) = &self.forced {
+      return Some(layout);
+    }
+
+    self.profiles.iter()
+      .filter_map(|layout| {
+        let score = layout.detect.as_ref()?.score(fingerprint)?;
+        Some((score, layout))
+      })
+      // 得分相同时使用后加载的配置，用户的配置优先于内置配置
+      .max_by_key(|(score, _)| *score)
+      .map(|(_, layout)| layout)
+  }
+
+  /*
+    所有的内存布局，包括 layout.toml
+  */
+  pub fn layouts(&self) -> impl Iterator<Item = &Layout> {
+    self.forced.iter().chain(self.profiles.iter())
+  }
+
+  /*
+    进程名是否是某个配置中的游戏执行文件
+  */
+  pub fn has_image_name(&self, name: &str) -> bool {
+    self.layouts()
+      .filter_map(|layout| layout.detect.as_ref()?.image_name.as_deref())
+      .any(|image_name| image_name.eq_ignore_ascii_case(name))
+  }
+}
+
+impl Default for Profiles {
+  fn default() -> Self {
+    Self::builtin()
+  }
+}
+
+pub(crate) fn search_dirs() -> Vec<PathBuf> {
+  let mut dirs: Vec<PathBuf> = vec![];
+  if let Ok(dir) = std::env::current_dir() {
+    dirs.push(dir);
+  }
+  if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf))
+    && !dirs.contains(&dir) {
+    dirs.push(dir);
+  }
+  dirs
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  /*
+    只有 PE 头的执行文件，magic 为可选头的 Magic
+  */
+  fn map_image(memory: &BufferMemory, machine: u16, magic: u16) {
+    let mut header = vec![0u8; HEADER_SIZE];
+    header[..2].copy_from_slice(b"MZ");
+    header[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    header[0x80..0x84].copy_from_slice(b"PE\0\0");
+    header[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+    header[0x88..0x8C].copy_from_slice(&0x3B5E0E8Au32.to_le_bytes());
+    header[0x98..0x9A].copy_from_slice(&magic.to_le_bytes());
+    header[0xD0..0xD4].copy_from_slice(&0x3A7000u32.to_le_bytes());
+    memory.map(DEFAULT_IMAGE_BASE, header);
+    memory.add_module("age2_x1.exe", DEFAULT_IMAGE_BASE);
+  }
+
+  #[test]
+  fn bitness_comes_from_pe_header() {
+    // 64 位的 wine 中运行的 32 位游戏
+    let memory = BufferMemory::with_bitness(Bitness::Bits64);
+    map_image(&memory, 0x14C, 0x10B);
+    assert_eq!(read_pe_bitness(&memory, "age2_x1.exe").unwrap(), Bitness::Bits32);
+
+    map_image(&memory, 0x8664, 0x20B);
+    assert_eq!(read_pe_bitness(&memory, "age2_x1.exe").unwrap(), Bitness::Bits64);
+
+    // 可选头无法识别时看 Machine
+    map_image(&memory, 0x14C, 0);
+    assert_eq!(read_pe_bitness(&memory, "age2_x1.exe").unwrap(), Bitness::Bits32);
+    map_image(&memory, 0x1C0, 0);
+    assert!(read_pe_bitness(&memory, "age2_x1.exe").is_err());
+  }
+
+  #[test]
+  fn reads_fingerprint() {
+    let memory = BufferMemory::new();
+    map_image(&memory, 0x14C, 0x10B);
+    let fingerprint = read_fingerprint(&memory, "age2_x1.exe").unwrap();
+    assert_eq!(fingerprint.image_size, 0x3A7000);
+    assert_eq!(fingerprint.timestamp, 0x3B5E0E8A);
+    assert_eq!(Profiles::builtin().detect(&fingerprint).map(|layout| layout.name.as_str()), Some("Age of Empires II: The Conquerors 1.0c"));
+
+    // 内置配置只按执行文件名识别
+    let other = Fingerprint { timestamp: 0x4E000000, ..fingerprint.clone() };
+    assert!(Profiles::builtin().detect(&other).is_some());
+    let renamed = Fingerprint { image_name: "empires2.exe".to_string(), ..fingerprint.clone() };
+    assert!(Profiles::builtin().detect(&renamed).is_none());
+
+    // 同名执行文件的其他版本填写了时间戳时优先使用
+    let mut profiles = Profiles::builtin();
+    let mut patched = profiles.profiles[0].clone();
+    patched.name = "UserPatch".to_string();
+    patched.detect.as_mut().unwrap().timestamp = Some(0x4E000000);
+    profiles.profiles.push(patched);
+    assert_eq!(profiles.detect(&other).map(|layout| layout.name.as_str()), Some("UserPatch"));
+    assert_eq!(profiles.detect(&fingerprint).map(|layout| layout.name.as_str()), Some("Age of Empires II: The Conquerors 1.0c"));
+  }
+}
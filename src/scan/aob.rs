@@ -0,0 +1,372 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::error::TrainerError;
+use crate::winapi::memory::{ProcessMemory, ProcessMemoryExt};
+
+// 扫描时每次读取的内存长度
+const CHUNK_SIZE: usize = 0x10000;
+// 读取 PE 头和节表的长度
+const HEADER_SIZE: usize = 0x1000;
+// 节的属性：包含代码、可执行
+const IMAGE_SCN_CNT_CODE: u32 = 0x00000020;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+
+
+/*
+  带通配符的字节特征码，例如 8B 0D ?? ?? ?? ?? 8B 81 A8 00 00 00
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+  bytes: Vec<Option<u8>>,
+}
+
+impl Pattern {
+  pub fn len(&self) -> usize {
+    self.bytes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.bytes.is_empty()
+  }
+
+  /*
+    data 的开头是否与特征码匹配
+  */
+  pub fn matches(&self, data: &[u8]) -> bool {
+    data.len() >= self.bytes.len()
+      && self.bytes.iter().zip(data).all(|(pattern, byte)| pattern.is_none_or(|pattern| pattern == *byte))
+  }
+
+  /*
+    在 data 中查找所有匹配的位置
+  */
+  pub fn find_all(&self, data: &[u8]) -> Vec<usize> {
+    if self.bytes.is_empty() || data.len() < self.bytes.len() {
+      return vec![];
+    }
+    (0..=data.len() - self.bytes.len())
+      .filter(|&i| self.matches(&data[i..]))
+      .collect()
+  }
+
+  /*
+    在目标进程的一段内存中查找所有匹配的地址，分块读取，无法读取的块会被跳过
+  */
+  pub fn scan(&self, memory: &dyn ProcessMemory, range: Range<usize>) -> Vec<usize> {
+    let mut results = vec![];
+    if self.bytes.is_empty() {
+      return results;
+    }
+
+    let mut start = range.start;
+    while start < range.end {
+      // 相邻的块重叠 len - 1 个字节，避免漏掉跨块的匹配
+      let end = (start + CHUNK_SIZE + self.bytes.len() - 1).min(range.end);
+      let mut data = vec![0u8; end - start];
+      if memory.read_bytes(start, &mut data).is_ok() {
+        results.extend(self.find_all(&data).into_iter().map(|i| start + i));
+      }
+      start += CHUNK_SIZE;
+    }
+    results
+  }
+
+  /*
+    在模块的代码节中查找所有匹配的地址
+  */
+  pub fn scan_module(&self, memory: &dyn ProcessMemory, module: &str) -> Result<Vec<usize>, TrainerError> {
+    let base = memory.module_base(module).ok_or_else(|| TrainerError::ModuleNotFound(module.to_string()))?;
+    let mut results = vec![];
+    for section in code_sections(memory, base)? {
+      results.extend(self.scan(memory, section));
+    }
+    Ok(results)
+  }
+}
+
+/*
+  解析特征码：两位十六进制数表示一个字节，?? 或 ? 表示任意字节，字节之间用空格分隔
+*/
+impl FromStr for Pattern {
+  type Err = TrainerError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let bytes = text.split_whitespace()
+      .map(|token| match token {
+        "?" | "??" => Ok(None),
+        _ if token.len() == 2 => u8::from_str_radix(token, 16).map(Some)
+          .map_err(|_| TrainerError::Signature(format!("invalid byte \"{}\" in pattern", token))),
+        _ => Err(TrainerError::Signature(format!("invalid byte \"{}\" in pattern", token))),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    if bytes.is_empty() {
+      return Err(TrainerError::Signature("pattern is empty".to_string()));
+    }
+    Ok(Self { bytes })
+  }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse().map_err(serde::de::Error::custom)
+  }
+}
+
+impl fmt::Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let text: Vec<String> = self.bytes.iter()
+      .map(|byte| byte.map_or("??".to_string(), |byte| format!("{:02X}", byte)))
+      .collect();
+    f.write_str(&text.join(" "))
+  }
+}
+
+
+/*
+  用特征码定位基址：找到引用基址的指令，再从指令的操作数中取出地址。
+  例如 32 位的 mov ecx,[0x007A5FEC] 编码为 8B 0D EC 5F 7A 00，操作数偏移为 2，是绝对地址；
+  64 位的 mov rcx,[rip+disp32] 编码为 48 8B 0D xx xx xx xx，操作数偏移为 3，地址为指令结束处加上 disp32
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct Signature {
+  pub pattern: Pattern,
+  // 操作数在匹配位置中的偏移
+  pub operand: usize,
+  // 操作数是否是相对于指令结束处的 32 位偏移（x64 的 RIP 相对寻址、call/jmp 的目标）
+  #[serde(default)]
+  pub relative: bool,
+  // 指令结束处在匹配位置中的偏移，不填时为 operand + 4（操作数后面没有立即数）
+  pub instruction_end: Option<usize>,
+}
+
+impl Signature {
+  /*
+    操作数的字节数：相对偏移为 4 字节，绝对地址为游戏进程的指针大小
+  */
+  pub fn operand_size(&self, pointer_size: usize) -> usize {
+    if self.relative { 4 } else { pointer_size }
+  }
+
+  /*
+    从匹配位置 addr 处的指令中取出操作数表示的地址
+  */
+  pub fn read_operand(&self, memory: &dyn ProcessMemory, addr: usize) -> Result<usize, TrainerError> {
+    let operand_addr = addr + self.operand;
+    if self.relative {
+      let displacement = memory.read::<i32>(operand_addr)?;
+      let instruction_end = addr + self.instruction_end.unwrap_or(self.operand + 4);
+      Ok(instruction_end.wrapping_add_signed(displacement as isize))
+    } else {
+      memory.read_pointer(operand_addr)
+    }
+  }
+
+  /*
+    在模块的代码节中查找特征码并取出地址。
+    没有匹配，或者多个匹配得到不同的地址时返回错误
+  */
+  pub fn find(&self, memory: &dyn ProcessMemory, module: &str) -> Result<usize, TrainerError> {
+    // 操作数必须在特征码之内，否则读到的是特征码之后任意的字节
+    if self.operand + self.operand_size(memory.bitness().pointer_size()) > self.pattern.len() {
+      return Err(TrainerError::Signature(format!("operand of \"{}\" is out of the pattern", self.pattern)));
+    }
+    let matches = self.pattern.scan_module(memory, module)?;
+    let mut addrs = matches.iter()
+      .map(|&addr| self.read_operand(memory, addr))
+      .collect::<Result<Vec<_>, _>>()?;
+    addrs.sort();
+    addrs.dedup();
+
+    match addrs[..] {
+      [addr] => Ok(addr),
+      [] => Err(TrainerError::Signature(format!("\"{}\" not found in {}", self.pattern, module))),
+      _ => Err(TrainerError::Signature(format!(
+        "\"{}\" matches {} different addresses in {}", self.pattern, addrs.len(), module
+      ))),
+    }
+  }
+}
+
+
+/*
+  读取模块的 PE 节表，返回包含代码的节的地址范围
+*/
+pub fn code_sections(memory: &dyn ProcessMemory, base: usize) -> Result<Vec<Range<usize>>, TrainerError> {
+  let invalid = || TrainerError::Signature(format!("invalid PE header at 0x{:X}", base));
+
+  let mut header = vec![0u8; HEADER_SIZE];
+  memory.read_bytes(base, &mut header)?;
+  let read_u16 = |offset: usize| header.get(offset..offset + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()));
+  let read_u32 = |offset: usize| header.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+
+  if &header[..2] != b"MZ" {
+    return Err(invalid());
+  }
+  let pe_offset = read_u32(0x3C).ok_or_else(invalid)? as usize;
+  if header.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+    return Err(invalid());
+  }
+  let section_count = read_u16(pe_offset + 6).ok_or_else(invalid)? as usize;
+  let optional_header_size = read_u16(pe_offset + 20).ok_or_else(invalid)? as usize;
+  // PE 签名(4) + 文件头(20) + 可选头，之后是每个 40 字节的节表
+  let section_table = pe_offset + 24 + optional_header_size;
+
+  let mut sections = vec![];
+  for i in 0..section_count {
+    let section = section_table + i * 40;
+    let virtual_size = read_u32(section + 8).ok_or_else(invalid)? as usize;
+    let virtual_address = read_u32(section + 12).ok_or_else(invalid)? as usize;
+    let characteristics = read_u32(section + 36).ok_or_else(invalid)?;
+    if characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0 {
+      sections.push(base + virtual_address..base + virtual_address + virtual_size);
+    }
+  }
+  Ok(sections)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::{BufferMemory, Bitness};
+
+  const BASE: usize = 0x400000;
+  const TEXT_RVA: usize = 0x1000;
+  const TEXT_SIZE: usize = 0x20000;
+
+  /*
+    构造一个只有 .text 和 .data 两个节的 PE 模块，映射在 BASE
+  */
+  fn synthetic_module(memory: &BufferMemory) -> Vec<u8> {
+    let mut image = vec![0u8; TEXT_RVA + TEXT_SIZE + 0x1000];
+    image[..2].copy_from_slice(b"MZ");
+    image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    image[0x86..0x88].copy_from_slice(&2u16.to_le_bytes());
+    image[0x94..0x96].copy_from_slice(&0xE0u16.to_le_bytes());
+    let sections = [
+      (TEXT_RVA, TEXT_SIZE, IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE),
+      (TEXT_RVA + TEXT_SIZE, 0x1000, 0xC0000040),
+    ];
+    for (i, (rva, size, characteristics)) in sections.into_iter().enumerate() {
+      let section = 0x80 + 24 + 0xE0 + i * 40;
+      image[section + 8..section + 12].copy_from_slice(&(size as u32).to_le_bytes());
+      image[section + 12..section + 16].copy_from_slice(&(rva as u32).to_le_bytes());
+      image[section + 36..section + 40].copy_from_slice(&characteristics.to_le_bytes());
+    }
+    memory.map(BASE, image.clone());
+    memory.add_module("game.exe", BASE);
+    image
+  }
+
+  #[test]
+  fn parses_pattern_with_wildcards() {
+    let pattern: Pattern = "8b 0D ? ?? 00".parse().unwrap();
+    assert_eq!(pattern.len(), 5);
+    assert_eq!(pattern.to_string(), "8B 0D ?? ?? 00");
+
+    assert!("8B GG".parse::<Pattern>().is_err());
+    assert!("8B0D".parse::<Pattern>().is_err());
+    assert!("".parse::<Pattern>().is_err());
+  }
+
+  #[test]
+  fn finds_all_matches_with_wildcards() {
+    let pattern: Pattern = "8B 0D ?? ??".parse().unwrap();
+    let data = [0x8B, 0x0D, 0x01, 0x02, 0x8B, 0x0D, 0x03, 0x04, 0x8B, 0x0D, 0x05];
+    assert_eq!(pattern.find_all(&data), vec![0, 4]);
+    assert_eq!(pattern.find_all(&data[..3]), Vec::<usize>::new());
+  }
+
+  #[test]
+  fn scan_finds_matches_across_chunks() {
+    let memory = BufferMemory::new();
+    let mut data = vec![0u8; CHUNK_SIZE * 2];
+    data[CHUNK_SIZE - 2..CHUNK_SIZE + 2].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    data[CHUNK_SIZE * 2 - 4..].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    memory.map(0x10000000, data);
+
+    let pattern: Pattern = "DE AD ?? EF".parse().unwrap();
+    assert_eq!(
+      pattern.scan(&memory, 0x10000000..0x10000000 + CHUNK_SIZE * 2),
+      vec![0x10000000 + CHUNK_SIZE - 2, 0x10000000 + CHUNK_SIZE * 2 - 4],
+    );
+  }
+
+  #[test]
+  fn reads_code_sections() {
+    let memory = BufferMemory::new();
+    synthetic_module(&memory);
+    assert_eq!(code_sections(&memory, BASE).unwrap(), vec![BASE + TEXT_RVA..BASE + TEXT_RVA + TEXT_SIZE]);
+
+    memory.map(0x800000, vec![0u8; HEADER_SIZE]);
+    assert!(code_sections(&memory, 0x800000).is_err());
+  }
+
+  #[test]
+  fn reads_absolute_operand() {
+    let memory = BufferMemory::new();
+    memory.map(0x1000, vec![0x8B, 0x0D, 0xEC, 0x5F, 0x7A, 0x00]);
+    let signature = Signature { pattern: "8B 0D".parse().unwrap(), operand: 2, relative: false, instruction_end: None };
+    assert_eq!(signature.read_operand(&memory, 0x1000).unwrap(), 0x7A5FEC);
+  }
+
+  #[test]
+  fn reads_rip_relative_operand() {
+    let memory = BufferMemory::with_bitness(Bitness::Bits64);
+    let mut code = vec![0x48, 0x8B, 0x0D];
+    code.extend_from_slice(&0x100i32.to_le_bytes());
+    code.extend_from_slice(&[0x48, 0x8B, 0x0D]);
+    code.extend_from_slice(&(-0x20i32).to_le_bytes());
+    memory.map(0x140001000, code);
+
+    let signature = Signature { pattern: "48 8B 0D".parse().unwrap(), operand: 3, relative: true, instruction_end: None };
+    assert_eq!(signature.read_operand(&memory, 0x140001000).unwrap(), 0x140001007 + 0x100);
+    assert_eq!(signature.read_operand(&memory, 0x140001007).unwrap(), 0x14000100E - 0x20);
+
+    // 操作数后面还有 1 字节的立即数
+    let signature = Signature { instruction_end: Some(8), ..signature };
+    assert_eq!(signature.read_operand(&memory, 0x140001000).unwrap(), 0x140001008 + 0x100);
+  }
+
+  #[test]
+  fn finds_signature_in_code_sections_only() {
+    let memory = BufferMemory::new();
+    let mut image = synthetic_module(&memory);
+    let code = [0x8B, 0x0D, 0xEC, 0x5F, 0x7A, 0x00, 0x8B, 0x81];
+    image[TEXT_RVA + 0x100..TEXT_RVA + 0x108].copy_from_slice(&code);
+    image[TEXT_RVA + 0x200..TEXT_RVA + 0x208].copy_from_slice(&code);
+    // .data 中的相同字节不会被当作代码
+    image[TEXT_RVA + TEXT_SIZE + 0x10..TEXT_RVA + TEXT_SIZE + 0x18].copy_from_slice(&[0x8B, 0x0D, 0, 0, 0, 0, 0x8B, 0x81]);
+    memory.map(BASE, image.clone());
+
+    let signature = Signature { pattern: "8B 0D ?? ?? ?? ?? 8B 81".parse().unwrap(), operand: 2, relative: false, instruction_end: None };
+    assert_eq!(signature.find(&memory, "GAME.EXE").unwrap(), 0x7A5FEC);
+
+    image[TEXT_RVA + 0x202] = 0xF0;
+    memory.map(BASE, image);
+    assert!(signature.find(&memory, "game.exe").is_err());
+    assert!(signature.find(&memory, "other.exe").is_err());
+  }
+
+  #[test]
+  fn reads_pointer_sized_absolute_operand() {
+    // 64 位的 mov rax,[moffs64] 编码为 48 A1 加上 8 字节的地址
+    let memory = BufferMemory::with_bitness(Bitness::Bits64);
+    let mut image = synthetic_module(&memory);
+    image[TEXT_RVA + 0x100..TEXT_RVA + 0x10C].copy_from_slice(&[0x48, 0xA1, 0xEC, 0x5F, 0x7A, 0x40, 0x01, 0, 0, 0, 0xC3, 0x90]);
+    memory.map(BASE, image);
+
+    let signature = Signature { pattern: "48 A1 ?? ?? ?? ?? ?? ?? ?? ?? C3".parse().unwrap(), operand: 2, relative: false, instruction_end: None };
+    assert_eq!(signature.find(&memory, "game.exe").unwrap(), 0x1407A5FEC);
+
+    // 特征码只覆盖了 4 字节的操作数
+    let signature = Signature { pattern: "48 A1 ?? ?? ?? ??".parse().unwrap(), ..signature };
+    assert!(matches!(signature.find(&memory, "game.exe"), Err(TrainerError::Signature(_))));
+  }
+}
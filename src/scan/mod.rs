@@ -0,0 +1,7 @@
+pub mod aob;
+pub mod pointer;
+pub mod value;
+
+pub use aob::{Pattern, Signature};
+pub use pointer::PointerScanner;
+pub use value::{ScanFilter, ValueScanner};
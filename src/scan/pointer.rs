@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::TrainerError;
+use crate::winapi::memory::{ModuleInfo, ProcessMemory};
+use crate::winapi::pointer::{PointerBase, PointerChain};
+
+// 建立指针表时每次读取的内存长度
+const CHUNK_SIZE: usize = 0x100000;
+
+
+/*
+  指针扫描：查找从模块中的静态地址出发、经过若干级指针到达目标地址的指针链。
+  先扫描所有可读内存，记录每个指向可读内存的指针，再从目标地址开始逐级向前查找
+*/
+#[derive(Debug, Clone)]
+pub struct PointerScanner {
+  // 指针链的最大级数
+  pub max_depth: usize,
+  // 每一级的最大偏移
+  pub max_offset: usize,
+  // 找到这么多结果后停止扫描
+  pub max_results: usize,
+}
+
+impl Default for PointerScanner {
+  fn default() -> Self {
+    Self { max_depth: 4, max_offset: 0x1000, max_results: 10000 }
+  }
+}
+
+/*
+  扫描过程中使用的数据
+*/
+struct ScanContext<'a> {
+  // 按指针的值排序的 (指针的值, 指针所在的地址)
+  pointers: Vec<(usize, usize)>,
+  modules: &'a [ModuleInfo],
+  // 在剩余的级数内找不到静态地址的 (地址, 剩余级数)
+  dead_ends: HashSet<(usize, usize)>,
+}
+
+impl PointerScanner {
+  pub fn new(max_depth: usize, max_offset: usize) -> Self {
+    Self { max_depth, max_offset, ..Self::default() }
+  }
+
+  /*
+    查找所有指向 target 的指针链，结果按级数从少到多排序
+  */
+  pub fn scan(&self, memory: &dyn ProcessMemory, target: usize) -> Result<Vec<PointerChain>, TrainerError> {
+    if self.max_depth == 0 {
+      return Err(TrainerError::Scan("max depth must be at least 1".to_string()));
+    }
+
+    let modules = memory.modules();
+    if modules.is_empty() {
+      return Err(TrainerError::Scan("no modules found in process".to_string()));
+    }
+
+    let mut context = ScanContext {
+      pointers: build_pointer_map(memory),
+      modules: &modules,
+      dead_ends: HashSet::new(),
+    };
+    let mut results = vec![];
+    self.search(&mut context, target, self.max_depth, &mut vec![], &mut results);
+
+    results.sort_by_key(|chain| chain.offsets.len());
+    Ok(results)
+  }
+
+  /*
+    查找指向 target 附近的指针，offsets 中保存的是从 target 往回已经经过的偏移。
+    返回是否找到了结果
+  */
+  fn search(
+    &self,
+    context: &mut ScanContext,
+    target: usize,
+    remaining: usize,
+    offsets: &mut Vec<usize>,
+    results: &mut Vec<PointerChain>,
+  ) -> bool {
+    if results.len() >= self.max_results || context.dead_ends.contains(&(target, remaining)) {
+      return false;
+    }
+
+    let low = target.saturating_sub(self.max_offset);
+    let start = context.pointers.partition_point(|(value, _)| *value < low);
+    let end = context.pointers.partition_point(|(value, _)| *value <= target);
+
+    let mut found = false;
+    for i in start..end {
+      let (value, addr) = context.pointers[i];
+      offsets.push(target - value);
+
+      if let Some(module) = context.modules.iter().find(|module| addr >= module.base && addr < module.base + module.size) {
+        results.push(PointerChain {
+          base: PointerBase::Module { name: module.name.clone(), offset: addr - module.base },
+          offsets: offsets.iter().rev().copied().collect(),
+        });
+        found = true;
+      } else if remaining > 1 && self.search(context, addr, remaining - 1, offsets, results) {
+        found = true;
+      }
+
+      offsets.pop();
+      if results.len() >= self.max_results {
+        break;
+      }
+    }
+
+    if !found {
+      context.dead_ends.insert((target, remaining));
+    }
+    found
+  }
+}
+
+/*
+  扫描所有可读内存中按指针长度对齐、并且指向可读内存的值
+*/
+fn build_pointer_map(memory: &dyn ProcessMemory) -> Vec<(usize, usize)> {
+  let mut regions = memory.readable_regions();
+  regions.sort_by_key(|region| region.start);
+  let is_readable = |value: usize| {
+    let i = regions.partition_point(|region| region.end <= value);
+    regions.get(i).is_some_and(|region| region.contains(&value))
+  };
+  let pointer_size = memory.bitness().pointer_size();
+
+  let mut pointers = vec![];
+  for region in &regions {
+    let mut base = region.start;
+    while base < region.end {
+      let end = (base + CHUNK_SIZE).min(region.end);
+      let mut data = vec![0u8; end - base];
+      if memory.read_bytes(base, &mut data).is_ok() {
+        for (i, bytes) in data.chunks_exact(pointer_size).enumerate() {
+          let value = match pointer_size {
+            4 => u32::from_le_bytes(bytes.try_into().unwrap()) as usize,
+            _ => u64::from_le_bytes(bytes.try_into().unwrap()) as usize,
+          };
+          if value != 0 && is_readable(value) {
+            pointers.push((value, base + i * pointer_size));
+          }
+        }
+      }
+      base = end;
+    }
+  }
+
+  pointers.sort_unstable();
+  pointers
+}
+
+/*
+  只保留在当前进程中仍然指向 target 的指针链，用于在游戏重启后排除不稳定的结果
+*/
+pub fn filter_valid(memory: &dyn ProcessMemory, chains: &[PointerChain], target: usize) -> Vec<PointerChain> {
+  chains.iter()
+    .filter(|chain| chain.resolve(memory).is_ok_and(|addr| addr == target))
+    .cloned()
+    .collect()
+}
+
+/*
+  把扫描结果保存为文本文件，每行一个 Cheat Engine 写法的指针链
+*/
+pub fn save_chains(path: &Path, chains: &[PointerChain]) -> Result<(), TrainerError> {
+  let text: String = chains.iter().map(|chain| format!("{}\n", chain)).collect();
+  std::fs::write(path, text)?;
+  Ok(())
+}
+
+pub fn load_chains(path: &Path) -> Result<Vec<PointerChain>, TrainerError> {
+  std::fs::read_to_string(path)?
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(str::parse)
+    .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  /*
+    game.exe+0x10 -> 0x2000，0x2000+0x8 -> 0x3000；game.exe+0x20 -> 0x3000
+  */
+  fn game_memory() -> BufferMemory {
+    let memory = BufferMemory::new();
+    let mut exe = vec![0u8; 0x100];
+    exe[0x10..0x14].copy_from_slice(&0x2000u32.to_le_bytes());
+    exe[0x20..0x24].copy_from_slice(&0x3000u32.to_le_bytes());
+    memory.map(0x400000, exe);
+    memory.add_module("game.exe", 0x400000);
+    let mut object = vec![0u8; 0x100];
+    object[0x8..0xC].copy_from_slice(&0x3000u32.to_le_bytes());
+    memory.map(0x2000, object);
+    memory.map(0x3000, vec![0u8; 0x100]);
+    memory
+  }
+
+  #[test]
+  fn finds_chains_from_static_addresses() {
+    let memory = game_memory();
+    let chains = PointerScanner::new(2, 0x100).scan(&memory, 0x3020).unwrap();
+    assert_eq!(chains, vec![
+      PointerChain::module("game.exe", 0x20, &[0x20]),
+      PointerChain::module("game.exe", 0x10, &[0x8, 0x20]),
+    ]);
+    for chain in &chains {
+      assert_eq!(chain.resolve(&memory).unwrap(), 0x3020);
+    }
+
+    assert_eq!(PointerScanner::new(1, 0x100).scan(&memory, 0x3020).unwrap().len(), 1);
+    assert!(PointerScanner::new(2, 0x10).scan(&memory, 0x3020).unwrap().is_empty());
+    assert!(PointerScanner::new(0, 0x100).scan(&memory, 0x3020).is_err());
+  }
+
+  #[test]
+  fn stops_at_max_results() {
+    let memory = game_memory();
+    let scanner = PointerScanner { max_results: 1, ..PointerScanner::new(2, 0x100) };
+    assert_eq!(scanner.scan(&memory, 0x3020).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn filters_chains_that_no_longer_point_to_target() {
+    let memory = game_memory();
+    let chains = PointerScanner::new(2, 0x100).scan(&memory, 0x3020).unwrap();
+    memory.write_bytes(0x2008, &0x3080u32.to_le_bytes()).unwrap();
+    assert_eq!(filter_valid(&memory, &chains, 0x3020), vec![PointerChain::module("game.exe", 0x20, &[0x20])]);
+  }
+}
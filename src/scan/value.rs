@@ -0,0 +1,301 @@
+use crate::error::TrainerError;
+use crate::value::{Value, ValueType};
+use crate::winapi::memory::ProcessMemory;
+
+// 首次扫描时每次读取的内存长度，同一块中的结果用相对于块起始地址的 u32 偏移保存
+const CHUNK_SIZE: usize = 0x100000;
+
+
+/*
+  扫描条件。首次扫描只能使用 Exact 和 Range，其它条件与上一次扫描的值比较
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanFilter {
+  // 等于指定的值
+  Exact(f64),
+  // 在 [min, max] 范围内
+  Range(f64, f64),
+  // 与上次的值不同
+  Changed,
+  // 与上次的值相同
+  Unchanged,
+  // 比上次的值大
+  Increased,
+  // 比上次的值小
+  Decreased,
+}
+
+impl ScanFilter {
+  fn needs_previous(&self) -> bool {
+    !matches!(self, ScanFilter::Exact(_) | ScanFilter::Range(..))
+  }
+
+  /*
+    current 和 previous 都是数值的原始字节
+  */
+  fn matches(&self, value_type: ValueType, current: &[u8], previous: &[u8], exact: Option<&ExactValue>) -> bool {
+    match self {
+      ScanFilter::Exact(_) => match exact {
+        Some(ExactValue::Bytes(bytes)) => current == bytes,
+        Some(ExactValue::Float { value, tolerance }) => (decode(value_type, current) - value).abs() < *tolerance,
+        None => false,
+      },
+      ScanFilter::Changed => current != previous,
+      ScanFilter::Unchanged => current == previous,
+      ScanFilter::Range(min, max) => {
+        let value = decode(value_type, current);
+        value >= *min && value <= *max
+      }
+      ScanFilter::Increased => decode(value_type, current) > decode(value_type, previous),
+      ScanFilter::Decreased => decode(value_type, current) < decode(value_type, previous),
+    }
+  }
+}
+
+fn decode(value_type: ValueType, bytes: &[u8]) -> f64 {
+  Value::from_bytes(value_type, bytes).map_or(f64::NAN, |value| value.as_f64())
+}
+
+/*
+  一块内存中的扫描结果：地址保存为相对于 base 的 u32 偏移，上次的值按类型大小连续保存，
+  每个结果只占用 4 + 类型大小个字节
+*/
+#[derive(Debug, Clone)]
+struct ScanChunk {
+  base: usize,
+  offsets: Vec<u32>,
+  values: Vec<u8>,
+}
+
+/*
+  类似 Cheat Engine 的数值扫描：先按值做首次扫描，再根据数值的变化不断缩小范围
+*/
+#[derive(Debug, Clone)]
+pub struct ValueScanner {
+  pub value_type: ValueType,
+  // 只扫描按类型大小对齐的地址，速度更快，游戏中的数值通常都是对齐的
+  pub aligned: bool,
+  chunks: Vec<ScanChunk>,
+}
+
+impl ValueScanner {
+  pub fn new(value_type: ValueType) -> Self {
+    Self { value_type, aligned: true, chunks: vec![] }
+  }
+
+  /*
+    结果的数量
+  */
+  pub fn count(&self) -> usize {
+    self.chunks.iter().map(|chunk| chunk.offsets.len()).sum()
+  }
+
+  /*
+    所有结果的地址和上一次扫描时的值
+  */
+  pub fn results(&self) -> impl Iterator<Item = (usize, Value)> + '_ {
+    let size = self.value_type.size();
+    self.chunks.iter().flat_map(move |chunk| {
+      chunk.offsets.iter().zip(chunk.values.chunks_exact(size)).map(move |(offset, bytes)| {
+        (chunk.base + *offset as usize, Value::from_bytes(self.value_type, bytes).unwrap())
+      })
+    })
+  }
+
+  pub fn clear(&mut self) {
+    self.chunks.clear();
+  }
+
+  /*
+    扫描所有可读的内存区域，返回结果的数量
+  */
+  pub fn first_scan(&mut self, memory: &dyn ProcessMemory, filter: ScanFilter) -> Result<usize, TrainerError> {
+    if filter.needs_previous() {
+      return Err(TrainerError::Scan(format!("{:?} needs a previous scan", filter)));
+    }
+
+    let size = self.value_type.size();
+    let step = if self.aligned { size } else { 1 };
+    let exact = exact_value(self.value_type, filter);
+    self.chunks.clear();
+
+    for region in memory.readable_regions() {
+      let mut base = region.start;
+      while base < region.end {
+        let end = (base + CHUNK_SIZE).min(region.end);
+        let mut data = vec![0u8; end - base];
+        let read = memory.read_bytes(base, &mut data);
+        let chunk_base = base;
+        base = end;
+        // 区域可能在枚举之后被释放，读取失败时跳过
+        if read.is_err() {
+          continue;
+        }
+
+        let mut chunk = ScanChunk { base: chunk_base, offsets: vec![], values: vec![] };
+        let mut offset = 0;
+        while offset + size <= data.len() {
+          let current = &data[offset..offset + size];
+          if filter.matches(self.value_type, current, &[], exact.as_ref()) {
+            chunk.offsets.push(offset as u32);
+            chunk.values.extend_from_slice(current);
+          }
+          offset += step;
+        }
+        if !chunk.offsets.is_empty() {
+          chunk.offsets.shrink_to_fit();
+          chunk.values.shrink_to_fit();
+          self.chunks.push(chunk);
+        }
+      }
+    }
+
+    Ok(self.count())
+  }
+
+  /*
+    重新读取上次的结果，只保留满足条件的地址，返回剩余结果的数量
+  */
+  pub fn next_scan(&mut self, memory: &dyn ProcessMemory, filter: ScanFilter) -> Result<usize, TrainerError> {
+    let size = self.value_type.size();
+    let exact = exact_value(self.value_type, filter);
+
+    for chunk in &mut self.chunks {
+      let (Some(first), Some(last)) = (chunk.offsets.first(), chunk.offsets.last()) else {
+        continue;
+      };
+      // 一次读取块中从第一个到最后一个结果的范围
+      let start = chunk.base + *first as usize;
+      let mut data = vec![0u8; (*last - *first) as usize + size];
+      if memory.read_bytes(start, &mut data).is_err() {
+        chunk.offsets.clear();
+        chunk.values.clear();
+        continue;
+      }
+
+      let mut offsets = vec![];
+      let mut values = vec![];
+      for (offset, previous) in chunk.offsets.iter().zip(chunk.values.chunks_exact(size)) {
+        let index = (*offset - *first) as usize;
+        let current = &data[index..index + size];
+        if filter.matches(self.value_type, current, previous, exact.as_ref()) {
+          offsets.push(*offset);
+          values.extend_from_slice(current);
+        }
+      }
+      chunk.offsets = offsets;
+      chunk.values = values;
+    }
+    self.chunks.retain(|chunk| !chunk.offsets.is_empty());
+
+    Ok(self.count())
+  }
+}
+
+/*
+  Exact 条件预先计算的比较值
+*/
+#[derive(Debug, Clone)]
+enum ExactValue {
+  // 整数按类型转换为字节，扫描时直接比较字节
+  Bytes(Vec<u8>),
+  // 浮点数按输入的精度比较，差值小于 tolerance 时匹配
+  Float { value: f64, tolerance: f64 },
+}
+
+/*
+  浮点数的精度由输入的小数位数决定：输入 200 时 199.5 到 200.5 之间的值都匹配（游戏中显示的是取整后的值），
+  输入 1.25 时 1.245 到 1.255 之间的值都匹配
+*/
+fn exact_value(value_type: ValueType, filter: ScanFilter) -> Option<ExactValue> {
+  let ScanFilter::Exact(value) = filter else {
+    return None;
+  };
+  match value_type {
+    ValueType::F32 | ValueType::F64 => {
+      let text = value.to_string();
+      let decimals = text.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+      Some(ExactValue::Float { value, tolerance: 0.5 * 10f64.powi(-(decimals as i32)) })
+    }
+    _ => Some(ExactValue::Bytes(Value::from_f64(value_type, value).to_bytes())),
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  fn write_i32(memory: &BufferMemory, addr: usize, value: i32) {
+    memory.write_bytes(addr, &value.to_le_bytes()).unwrap();
+  }
+
+  #[test]
+  fn narrows_results_with_next_scan() {
+    let memory = BufferMemory::new();
+    memory.map(0x10000, vec![0u8; 0x100]);
+    // 跨过首次扫描的分块
+    memory.map(0x20000000, vec![0u8; CHUNK_SIZE + 0x10]);
+    write_i32(&memory, 0x10010, 100);
+    write_i32(&memory, 0x10020, 100);
+    write_i32(&memory, 0x20000000 + CHUNK_SIZE + 4, 100);
+
+    let mut scanner = ValueScanner::new(ValueType::I32);
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Exact(100.0)).unwrap(), 3);
+    let addrs: Vec<usize> = scanner.results().map(|(addr, _)| addr).collect();
+    assert_eq!(addrs, vec![0x10010, 0x10020, 0x20000000 + CHUNK_SIZE + 4]);
+
+    write_i32(&memory, 0x10010, 150);
+    write_i32(&memory, 0x10020, 50);
+    assert_eq!(scanner.next_scan(&memory, ScanFilter::Changed).unwrap(), 2);
+    assert_eq!(scanner.next_scan(&memory, ScanFilter::Unchanged).unwrap(), 2);
+    assert_eq!(scanner.next_scan(&memory, ScanFilter::Range(100.0, 200.0)).unwrap(), 1);
+    assert_eq!(scanner.results().collect::<Vec<_>>(), vec![(0x10010, Value::I32(150))]);
+
+    write_i32(&memory, 0x10010, 149);
+    assert_eq!(scanner.next_scan(&memory, ScanFilter::Increased).unwrap(), 0);
+  }
+
+  #[test]
+  fn unaligned_scan_and_decreased_filter() {
+    let memory = BufferMemory::new();
+    memory.map(0x10000, vec![0u8; 0x20]);
+    memory.write_bytes(0x10003, &1.5f32.to_le_bytes()).unwrap();
+
+    let mut scanner = ValueScanner::new(ValueType::F32);
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Exact(1.5)).unwrap(), 0);
+    scanner.aligned = false;
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Range(1.0, 2.0)).unwrap(), 1);
+
+    memory.write_bytes(0x10003, &0.5f32.to_le_bytes()).unwrap();
+    assert_eq!(scanner.next_scan(&memory, ScanFilter::Decreased).unwrap(), 1);
+    assert_eq!(scanner.results().next(), Some((0x10003, Value::F32(0.5))));
+  }
+
+  #[test]
+  fn first_scan_needs_a_value() {
+    let memory = BufferMemory::new();
+    let mut scanner = ValueScanner::new(ValueType::I32);
+    assert!(scanner.first_scan(&memory, ScanFilter::Changed).is_err());
+  }
+
+  #[test]
+  fn matches_floats_at_entered_precision() {
+    let memory = BufferMemory::new();
+    memory.map(0x10000, vec![0u8; 0x100]);
+    // 游戏中显示为 200 的食物，实际的值有误差
+    memory.write_bytes(0x10010, &199.99997f32.to_le_bytes()).unwrap();
+    memory.write_bytes(0x10020, &200.4f32.to_le_bytes()).unwrap();
+    memory.write_bytes(0x10030, &201.0f32.to_le_bytes()).unwrap();
+    memory.write_bytes(0x10040, &1.2504f32.to_le_bytes()).unwrap();
+
+    let mut scanner = ValueScanner::new(ValueType::F32);
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Exact(200.0)).unwrap(), 2);
+    let addrs: Vec<usize> = scanner.results().map(|(addr, _)| addr).collect();
+    assert_eq!(addrs, vec![0x10010, 0x10020]);
+
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Exact(1.25)).unwrap(), 1);
+    assert_eq!(scanner.first_scan(&memory, ScanFilter::Exact(1.26)).unwrap(), 0);
+  }
+}
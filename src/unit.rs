@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::TrainerError;
+use crate::freeze::FreezeMode;
+use crate::layout::{AttrDef, Layout, UnitsDef};
+use crate::value::Value;
+use crate::winapi::memory::{ProcessMemory, ProcessMemoryExt};
+use crate::winapi::pointer::{PointerBase, PointerChain};
+
+// 单位数量的上限，读到更大的数量时认为布局中的单位列表有误
+const MAX_UNITS: usize = 100000;
+
+
+/*
+  一个单位的数据
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitInfo {
+  // 单位结构体的地址
+  pub address: usize,
+  pub id: i32,
+  pub type_id: i32,
+  // 所属玩家的编号，布局中没有填写 owner 时为 None
+  pub owner: Option<i32>,
+  pub hit_points: f32,
+  pub x: f32,
+  pub y: f32,
+  // 当前行动的编号，没有行动时为 None
+  pub action: Option<i32>,
+}
+
+/*
+  读取玩家的所有单位，player 为 None 时为本地玩家。
+  单位在读取过程中可能被删除，读取失败的单位会被跳过
+*/
+pub fn read_units(memory: &dyn ProcessMemory, layout: &Layout, player: Option<usize>) -> Result<Vec<UnitInfo>, TrainerError> {
+  let units = layout.units.as_ref()
+    .ok_or_else(|| TrainerError::Layout(format!("layout \"{}\" has no [units] section", layout.name)))?;
+  let pointer_size = memory.bitness().pointer_size();
+  let player_chain = |offsets: &[usize]| {
+    layout.player_chain(player, pointer_size, offsets)
+      .ok_or_else(|| TrainerError::Layout(format!("player {:?} is not in the player array", player)))
+  };
+
+  let count_addr = player_chain(&units.count.offsets)?.resolve(memory)?;
+  let count = Value::read(memory, count_addr, units.count.value_type)?.as_f64();
+  if count <= 0.0 {
+    return Ok(vec![]);
+  }
+  if count > MAX_UNITS as f64 {
+    return Err(TrainerError::Layout(format!("unit count {} is out of range, check the [units] offsets", count)));
+  }
+
+  let array = memory.read_pointer(player_chain(&units.list)?.resolve(memory)?)?;
+  if array == 0 {
+    return Ok(vec![]);
+  }
+  // 一次读取整个单位指针数组
+  let mut data = vec![0u8; count as usize * pointer_size];
+  memory.read_bytes(array, &mut data)?;
+
+  let result = data.chunks_exact(pointer_size)
+    .map(|bytes| match pointer_size {
+      4 => u32::from_le_bytes(bytes.try_into().unwrap()) as usize,
+      _ => u64::from_le_bytes(bytes.try_into().unwrap()) as usize,
+    })
+    .filter(|&address| address != 0)
+    .filter_map(|address| read_unit(memory, units, address).ok())
+    .collect();
+  Ok(result)
+}
+
+/*
+  读取一个单位。直接位于单位结构体中的数值从一次读取的整块内存中解析，需要经过指针的数值单独读取
+*/
+pub fn read_unit(memory: &dyn ProcessMemory, units: &UnitsDef, address: usize) -> Result<UnitInfo, TrainerError> {
+  let attrs = [Some(&units.id), Some(&units.type_id), units.owner.as_ref(), Some(&units.hit_points), Some(&units.x), Some(&units.y), units.action.as_ref()];
+  let size = attrs.iter().flatten()
+    .filter(|attr| attr.offsets.len() == 1)
+    .map(|attr| attr.offsets[0] + attr.value_type.size())
+    .max()
+    .unwrap_or(0);
+  let mut data = vec![0u8; size];
+  memory.read_bytes(address, &mut data)?;
+
+  let read = |attr: &AttrDef| -> Result<f64, TrainerError> {
+    if let [first] = attr.offsets[..] {
+      return Ok(Value::from_bytes(attr.value_type, &data[first..first + attr.value_type.size()])?.as_f64());
+    }
+    Ok(Value::read(memory, attr_address(memory, attr, address)?, attr.value_type)?.as_f64())
+  };
+
+  let action = match &units.action {
+    Some(attr) => match read(attr) {
+      Ok(action) => Some(action as i32),
+      // 单位没有行动
+      Err(TrainerError::NullPointer { .. }) => None,
+      Err(e) => return Err(e),
+    },
+    None => None,
+  };
+
+  Ok(UnitInfo {
+    address,
+    id: read(&units.id)? as i32,
+    type_id: read(&units.type_id)? as i32,
+    owner: units.owner.as_ref().map(read).transpose()?.map(|owner| owner as i32),
+    hit_points: read(&units.hit_points)? as f32,
+    x: read(&units.x)? as f32,
+    y: read(&units.y)? as f32,
+    action,
+  })
+}
+
+/*
+  单位结构体中数值的地址：第一个偏移直接加在单位的地址上，之后每一级先读取指针再加上偏移
+*/
+pub fn attr_address(memory: &dyn ProcessMemory, attr: &AttrDef, address: usize) -> Result<usize, TrainerError> {
+  let (&first, rest) = attr.offsets.split_first()
+    .ok_or_else(|| TrainerError::Layout("unit attribute has no offsets".to_string()))?;
+  let chain = PointerChain { base: PointerBase::Absolute(address.wrapping_add(first)), offsets: rest.to_vec() };
+  chain.resolve(memory)
+}
+
+/*
+  按单位编号或者单位类型选择单位
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSelector {
+  Unit(i32),
+  Type(i32),
+}
+
+impl UnitSelector {
+  pub fn matches(&self, unit: &UnitInfo) -> bool {
+    match *self {
+      UnitSelector::Unit(id) => unit.id == id,
+      UnitSelector::Type(type_id) => unit.type_id == type_id,
+    }
+  }
+}
+
+/*
+  修改前记录的类型属性：原来的字节和写入的字节
+*/
+#[derive(Debug, Clone)]
+struct SavedAttr {
+  original: Vec<u8>,
+  written: Vec<u8>,
+}
+
+/*
+  修改单位的生命值和类型属性。
+  类型属性（攻击、护甲、速度、视野等）由同一玩家同一类型的单位共用，第一次修改前记录原来的值，可以用 restore 恢复。
+  记录的地址属于修改时的玩家结构体，玩家结构体的指针变化（换了一局游戏）或者变为空指针之后直接丢弃，不再恢复
+*/
+#[derive(Debug, Clone, Default)]
+pub struct UnitEditor {
+  // 修改的玩家，None 为本地玩家
+  player: Option<usize>,
+  // 记录类型属性时玩家结构体的地址
+  owner: Option<usize>,
+  // 类型属性的地址 -> 修改前后的字节
+  originals: HashMap<usize, SavedAttr>,
+}
+
+impl UnitEditor {
+  /*
+    修改玩家数组中第 player 个玩家的单位，player 为 None 时为本地玩家
+  */
+  pub fn new(player: Option<usize>) -> Self {
+    Self { player, ..Self::default() }
+  }
+
+  /*
+    是否有还未恢复的类型属性
+  */
+  pub fn is_modified(&self) -> bool {
+    !self.originals.is_empty()
+  }
+
+  /*
+    修改选中单位的属性，attribute 为 hit_points 时修改单位自己的生命值，否则修改布局中的类型属性。
+    返回实际写入的地址数，同一类型的单位只写入一次。单位可能在读取之后被删除，跳过读写失败的单位
+  */
+  pub fn set(
+    &mut self,
+    memory: &dyn ProcessMemory,
+    layout: &Layout,
+    units: &[UnitInfo],
+    selector: UnitSelector,
+    attribute: &str,
+    value: f64,
+  ) -> Result<usize, TrainerError> {
+    let units_def = layout.units.as_ref()
+      .ok_or_else(|| TrainerError::Layout(format!("layout \"{}\" has no [units] section", layout.name)))?;
+    let (attr, is_type) = match attribute {
+      "hit_points" => (units_def.hit_points.clone(), false),
+      name => match units_def.attribute(name) {
+        Some(attr) => (attr.attr(), true),
+        None => return Err(TrainerError::Layout(format!("unknown unit attribute \"{}\"", name))),
+      },
+    };
+
+    // 换了一局游戏时，之前记录的地址已经不属于当前的玩家
+    let owner = player_pointer(memory, layout, self.player)?;
+    if is_type && self.owner != owner {
+      self.originals.clear();
+      self.owner = owner;
+    }
+
+    let bytes = Value::from_f64(attr.value_type, value).to_bytes();
+    let mut written = HashSet::new();
+    for unit in units.iter().filter(|unit| selector.matches(unit)) {
+      let Ok(addr) = attr_address(memory, &attr, unit.address) else {
+        continue;
+      };
+      if written.contains(&addr) {
+        continue;
+      }
+      let mut original = vec![0u8; bytes.len()];
+      if memory.read_bytes(addr, &mut original).is_err() || memory.write_bytes(addr, &bytes).is_err() {
+        continue;
+      }
+      if is_type {
+        self.originals.entry(addr)
+          .and_modify(|saved| saved.written = bytes.clone())
+          .or_insert_with(|| SavedAttr { original, written: bytes.clone() });
+      }
+      written.insert(addr);
+    }
+    Ok(written.len())
+  }
+
+  /*
+    把修改过的类型属性恢复为原来的值，返回恢复的地址数。
+    玩家结构体已经不是修改时的那个，或者地址中已经不是写入的值（被游戏改写）时丢弃记录，不写入。
+    写入失败的地址保留，下次再恢复
+  */
+  pub fn restore(&mut self, memory: &dyn ProcessMemory, layout: &Layout) -> Result<usize, TrainerError> {
+    if self.originals.is_empty() {
+      return Ok(0);
+    }
+    let owner = match player_pointer(memory, layout, self.player) {
+      Ok(owner) => owner,
+      Err(e @ TrainerError::Layout(_)) => return Err(e),
+      // 游戏进程已经退出或者游戏已经结束
+      Err(_) => None,
+    };
+    if owner.is_none() || owner != self.owner {
+      self.originals.clear();
+      return Ok(0);
+    }
+
+    let mut restored = 0;
+    let mut error = None;
+    self.originals.retain(|addr, saved| {
+      let mut current = vec![0u8; saved.written.len()];
+      if memory.read_bytes(*addr, &mut current).is_err() || current != saved.written {
+        return false;
+      }
+      match memory.write_bytes(*addr, &saved.original) {
+        Ok(()) => {
+          restored += 1;
+          false
+        }
+        Err(e) => {
+          error = Some(e);
+          true
+        }
+      }
+    });
+    match error {
+      Some(e) => Err(e),
+      None => Ok(restored),
+    }
+  }
+}
+
+/*
+  玩家结构体的地址，游戏还没有开始（空指针）时为 None
+*/
+fn player_pointer(memory: &dyn ProcessMemory, layout: &Layout, player: Option<usize>) -> Result<Option<usize>, TrainerError> {
+  let chain = layout.player_base(player, memory.bitness().pointer_size())
+    .ok_or_else(|| TrainerError::Layout(format!("player {:?} is not in the player array", player)))?;
+  match chain.resolve(memory).and_then(|addr| memory.read_pointer(addr)) {
+    Ok(0) | Err(TrainerError::NullPointer { .. }) => Ok(None),
+    Ok(pointer) => Ok(Some(pointer)),
+    Err(e) => Err(e),
+  }
+}
+
+/*
+  无敌模式：按 mode 修改玩家所有单位的生命值，mode 中的数值是生命值占最大生命值的比例，
+  例如 AtLeast(1.0) 使单位一直保持满血。返回写入的单位数
+*/
+pub fn heal_units(memory: &dyn ProcessMemory, layout: &Layout, player: Option<usize>, mode: FreezeMode) -> Result<usize, TrainerError> {
+  let units_def = layout.units.as_ref()
+    .ok_or_else(|| TrainerError::Layout(format!("layout \"{}\" has no [units] section", layout.name)))?;
+  let max_hit_points = units_def.max_hit_points.as_ref()
+    .ok_or_else(|| TrainerError::Layout(format!("layout \"{}\" has no max_hit_points in [units]", layout.name)))?;
+
+  let mut written = 0;
+  for unit in read_units(memory, layout, player)? {
+    // 单位可能在读取之后被删除，跳过读写失败的单位
+    let Ok(max) = attr_address(memory, max_hit_points, unit.address)
+      .and_then(|addr| Value::read(memory, addr, max_hit_points.value_type)) else {
+      continue;
+    };
+    let max = max.as_f64();
+    if max <= 0.0 {
+      continue;
+    }
+    let Some(ratio) = mode.target(unit.hit_points as f64 / max) else {
+      continue;
+    };
+    let value = Value::from_f64(units_def.hit_points.value_type, ratio * max);
+    if let Ok(addr) = attr_address(memory, &units_def.hit_points, unit.address)
+      && value.write(memory, addr).is_ok() {
+      written += 1;
+    }
+  }
+  Ok(written)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::BufferMemory;
+
+  // 内置布局中保存本地玩家结构体指针的地址
+  const PLAYER_SLOT: usize = 0x400000 + 0x3A5FEC;
+  // 单位类型对象中的移动速度
+  const SPEED: usize = 0xCC;
+
+  /*
+    本地玩家在 0x10000，单位类型对象在 0x30000 和 0x30400，前两个单位是同一类型
+  */
+  fn game() -> (BufferMemory, Vec<UnitInfo>) {
+    let memory = BufferMemory::new();
+    memory.map(0x400000, vec![0u8; 0x10]);
+    memory.add_module("age2_x1.exe", 0x400000);
+    memory.map(PLAYER_SLOT, 0x10000u32.to_le_bytes().to_vec());
+    memory.map(0x10000, vec![0u8; 0x200]);
+
+    let mut units = vec![];
+    for (i, (type_address, type_id)) in [(0x30000, 4), (0x30000, 4), (0x30400, 83)].into_iter().enumerate() {
+      let address = 0x20000 + i * 0x400;
+      let mut data = vec![0u8; 0x100];
+      data[0xC..0x10].copy_from_slice(&(type_address as u32).to_le_bytes());
+      memory.map(address, data);
+      let mut data = vec![0u8; 0x200];
+      data[SPEED..SPEED + 4].copy_from_slice(&1.5f32.to_le_bytes());
+      memory.map(type_address, data);
+      units.push(UnitInfo { address, id: i as i32 + 1, type_id, owner: None, hit_points: 100.0, x: 0.0, y: 0.0, action: None });
+    }
+    (memory, units)
+  }
+
+  #[test]
+  fn sets_shared_type_once_and_restores() {
+    let (memory, units) = game();
+    let layout = Layout::builtin();
+    let mut editor = UnitEditor::new(None);
+    assert_eq!(editor.set(&memory, &layout, &units, UnitSelector::Type(4), "speed", 3.0).unwrap(), 1);
+    assert_eq!(memory.read::<f32>(0x30000 + SPEED).unwrap(), 3.0);
+    assert_eq!(memory.read::<f32>(0x30400 + SPEED).unwrap(), 1.5);
+
+    // 再次修改同一类型时保留第一次记录的原始值
+    assert_eq!(editor.set(&memory, &layout, &units, UnitSelector::Unit(2), "speed", 4.0).unwrap(), 1);
+    assert_eq!(editor.originals.len(), 1);
+    assert_eq!(editor.restore(&memory, &layout).unwrap(), 1);
+    assert_eq!(memory.read::<f32>(0x30000 + SPEED).unwrap(), 1.5);
+    assert!(!editor.is_modified());
+  }
+
+  #[test]
+  fn drops_originals_of_another_game() {
+    let (memory, units) = game();
+    let layout = Layout::builtin();
+    let mut editor = UnitEditor::new(None);
+    editor.set(&memory, &layout, &units, UnitSelector::Type(4), "speed", 3.0).unwrap();
+
+    // 新的一局游戏，玩家结构体换了地址，原来的地址可能已经被游戏另作他用
+    memory.map(0x50000, vec![0u8; 0x200]);
+    memory.write(PLAYER_SLOT, 0x50000u32).unwrap();
+    assert_eq!(editor.restore(&memory, &layout).unwrap(), 0);
+    assert_eq!(memory.read::<f32>(0x30000 + SPEED).unwrap(), 3.0);
+    assert!(!editor.is_modified());
+
+    // 游戏改写了修改的值之后不再恢复
+    editor.set(&memory, &layout, &units, UnitSelector::Type(83), "speed", 3.0).unwrap();
+    memory.write(0x30400 + SPEED, 2.0f32).unwrap();
+    assert_eq!(editor.restore(&memory, &layout).unwrap(), 0);
+    assert_eq!(memory.read::<f32>(0x30400 + SPEED).unwrap(), 2.0);
+  }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::error::TrainerError;
+use crate::winapi::memory::ProcessMemory;
+
+
+/*
+  内存中数值的类型
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+  U8,
+  I8,
+  U16,
+  I16,
+  U32,
+  I32,
+  U64,
+  I64,
+  F32,
+  F64,
+}
+
+impl ValueType {
+  pub fn size(self) -> usize {
+    match self {
+      ValueType::U8 | ValueType::I8 => 1,
+      ValueType::U16 | ValueType::I16 => 2,
+      ValueType::U32 | ValueType::I32 | ValueType::F32 => 4,
+      ValueType::U64 | ValueType::I64 | ValueType::F64 => 8,
+    }
+  }
+
+  pub fn is_float(self) -> bool {
+    matches!(self, ValueType::F32 | ValueType::F64)
+  }
+}
+
+impl fmt::Display for ValueType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      ValueType::U8 => "u8",
+      ValueType::I8 => "i8",
+      ValueType::U16 => "u16",
+      ValueType::I16 => "i16",
+      ValueType::U32 => "u32",
+      ValueType::I32 => "i32",
+      ValueType::U64 => "u64",
+      ValueType::I64 => "i64",
+      ValueType::F32 => "f32",
+      ValueType::F64 => "f64",
+    };
+    f.write_str(name)
+  }
+}
+
+
+/*
+  带类型的数值
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+  U8(u8),
+  I8(i8),
+  U16(u16),
+  I16(i16),
+  U32(u32),
+  I32(i32),
+  U64(u64),
+  I64(i64),
+  F32(f32),
+  F64(f64),
+}
+
+impl Value {
+  pub fn value_type(&self) -> ValueType {
+    match self {
+      Value::U8(_) => ValueType::U8,
+      Value::I8(_) => ValueType::I8,
+      Value::U16(_) => ValueType::U16,
+      Value::I16(_) => ValueType::I16,
+      Value::U32(_) => ValueType::U32,
+      Value::I32(_) => ValueType::I32,
+      Value::U64(_) => ValueType::U64,
+      Value::I64(_) => ValueType::I64,
+      Value::F32(_) => ValueType::F32,
+      Value::F64(_) => ValueType::F64,
+    }
+  }
+
+  /*
+    把数值转换为指定类型，整数类型会截断小数并限制在取值范围内
+  */
+  pub fn from_f64(value_type: ValueType, value: f64) -> Self {
+    match value_type {
+      ValueType::U8 => Value::U8(value as u8),
+      ValueType::I8 => Value::I8(value as i8),
+      ValueType::U16 => Value::U16(value as u16),
+      ValueType::I16 => Value::I16(value as i16),
+      ValueType::U32 => Value::U32(value as u32),
+      ValueType::I32 => Value::I32(value as i32),
+      ValueType::U64 => Value::U64(value as u64),
+      ValueType::I64 => Value::I64(value as i64),
+      ValueType::F32 => Value::F32(value as f32),
+      ValueType::F64 => Value::F64(value),
+    }
+  }
+
+  pub fn as_f64(&self) -> f64 {
+    match *self {
+      Value::U8(v) => v as f64,
+      Value::I8(v) => v as f64,
+      Value::U16(v) => v as f64,
+      Value::I16(v) => v as f64,
+      Value::U32(v) => v as f64,
+      Value::I32(v) => v as f64,
+      Value::U64(v) => v as f64,
+      Value::I64(v) => v as f64,
+      Value::F32(v) => v as f64,
+      Value::F64(v) => v,
+    }
+  }
+
+  /*
+    从小端字节序列解析数值，bytes 的长度必须与类型大小一致
+  */
+  pub fn from_bytes(value_type: ValueType, bytes: &[u8]) -> Result<Self, TrainerError> {
+    if bytes.len() != value_type.size() {
+      return Err(TrainerError::SizeMismatch { expected: value_type.size(), got: bytes.len() });
+    }
+    let value = match value_type {
+      ValueType::U8 => Value::U8(bytes[0]),
+      ValueType::I8 => Value::I8(bytes[0] as i8),
+      ValueType::U16 => Value::U16(u16::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::I16 => Value::I16(i16::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::U32 => Value::U32(u32::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::I32 => Value::I32(i32::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::U64 => Value::U64(u64::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::I64 => Value::I64(i64::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::F32 => Value::F32(f32::from_le_bytes(bytes.try_into().unwrap())),
+      ValueType::F64 => Value::F64(f64::from_le_bytes(bytes.try_into().unwrap())),
+    };
+    Ok(value)
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    match *self {
+      Value::U8(v) => vec![v],
+      Value::I8(v) => v.to_le_bytes().to_vec(),
+      Value::U16(v) => v.to_le_bytes().to_vec(),
+      Value::I16(v) => v.to_le_bytes().to_vec(),
+      Value::U32(v) => v.to_le_bytes().to_vec(),
+      Value::I32(v) => v.to_le_bytes().to_vec(),
+      Value::U64(v) => v.to_le_bytes().to_vec(),
+      Value::I64(v) => v.to_le_bytes().to_vec(),
+      Value::F32(v) => v.to_le_bytes().to_vec(),
+      Value::F64(v) => v.to_le_bytes().to_vec(),
+    }
+  }
+
+  pub fn read(memory: &dyn ProcessMemory, addr: usize, value_type: ValueType) -> Result<Self, TrainerError> {
+    let mut buf = vec![0u8; value_type.size()];
+    memory.read_bytes(addr, &mut buf)?;
+    Value::from_bytes(value_type, &buf)
+  }
+
+  pub fn write(&self, memory: &dyn ProcessMemory, addr: usize) -> Result<(), TrainerError> {
+    memory.write_bytes(addr, &self.to_bytes())
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::U8(v) => v.fmt(f),
+      Value::I8(v) => v.fmt(f),
+      Value::U16(v) => v.fmt(f),
+      Value::I16(v) => v.fmt(f),
+      Value::U32(v) => v.fmt(f),
+      Value::I32(v) => v.fmt(f),
+      Value::U64(v) => v.fmt(f),
+      Value::I64(v) => v.fmt(f),
+      Value::F32(v) => v.fmt(f),
+      Value::F64(v) => v.fmt(f),
+    }
+  }
+}
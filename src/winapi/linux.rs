@@ -0,0 +1,323 @@
+use std::ffi::c_void;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::TrainerError;
+use crate::profile;
+use super::memory::{Bitness, MemoryRegion, ModuleCache, ModuleInfo, ProcessMemory, Protection, RegionState};
+use super::process::{ProcessItem, ProcessManager};
+
+
+/*
+  Linux 下直接读写目标进程内存的后端，用于 Wine/Proton 中运行的游戏。
+  优先使用 process_vm_readv/process_vm_writev，失败时改用 /proc/<pid>/mem。
+  两种方式都需要 ptrace 权限（同一用户且 kernel.yama.ptrace_scope 允许，或者以 root 运行）。
+*/
+pub struct LinuxMemory {
+  pid: libc::pid_t,
+  mem_file: Mutex<Option<File>>,
+  bitness: Bitness,
+  module_cache: ModuleCache,
+}
+
+impl LinuxMemory {
+  /*
+    打开进程 pid，image_name 为游戏的执行文件名，用于从 PE 头判断进程位数
+  */
+  pub fn open(pid: u32, image_name: &str) -> Result<Self, TrainerError> {
+    if !Path::new(&format!("/proc/{}", pid)).exists() {
+      return Err(TrainerError::ProcessNotFound(pid));
+    }
+
+    let mut memory = Self {
+      pid: pid as libc::pid_t,
+      mem_file: Mutex::new(None),
+      bitness: read_elf_bitness(pid),
+      module_cache: ModuleCache::default(),
+    };
+    // 新式 WoW64 模式下 32 位的游戏也运行在 64 位的 wine 中，以游戏执行文件的 PE 头为准
+    if let Ok(bitness) = profile::read_pe_bitness(&memory, image_name) {
+      memory.bitness = bitness;
+    }
+    Ok(memory)
+  }
+
+  /*
+    按需打开 /proc/<pid>/mem，优先以读写方式打开，没有写权限时退回只读
+  */
+  fn with_mem_file<R>(&self, f: impl FnOnce(&File) -> io::Result<R>) -> io::Result<R> {
+    let mut mem_file = self.mem_file.lock().unwrap();
+    if mem_file.is_none() {
+      let path = format!("/proc/{}/mem", self.pid);
+      let file = OpenOptions::new().read(true).write(true).open(&path)
+        .or_else(|_| File::open(&path))?;
+      *mem_file = Some(file);
+    }
+    f(mem_file.as_ref().unwrap())
+  }
+
+  /*
+    把系统错误转换为 TrainerError
+  */
+  fn map_error(&self, err: io::Error, addr: usize) -> TrainerError {
+    match err.raw_os_error() {
+      Some(code @ (libc::EPERM | libc::EACCES)) => TrainerError::AccessDenied(code),
+      Some(libc::ESRCH) | Some(libc::ENOENT) => TrainerError::ProcessNotFound(self.pid as u32),
+      Some(libc::EIO) | Some(libc::EFAULT) => TrainerError::InvalidAddress(addr),
+      _ if err.kind() == io::ErrorKind::UnexpectedEof => TrainerError::InvalidAddress(addr),
+      _ => TrainerError::Io(err),
+    }
+  }
+}
+
+/*
+  根据 /proc/<pid>/exe 的 ELF 头判断进程位数（第 5 个字节：1 为 32 位，2 为 64 位）。
+  Wine 的执行文件是 wine-preloader/wine64-preloader，旧式 WoW64 下位数与游戏的 exe 一致；
+  只在读不到游戏的 PE 头时使用
+*/
+fn read_elf_bitness(pid: u32) -> Bitness {
+  let mut ident = [0u8; 5];
+  let class = File::open(format!("/proc/{}/exe", pid))
+    .and_then(|file| file.read_exact_at(&mut ident, 0))
+    .ok()
+    .filter(|_| &ident[..4] == b"\x7fELF")
+    .map(|_| ident[4]);
+  match class {
+    Some(1) => Bitness::Bits32,
+    Some(2) => Bitness::Bits64,
+    _ if cfg!(target_pointer_width = "64") => Bitness::Bits64,
+    _ => Bitness::Bits32,
+  }
+}
+
+impl ProcessMemory for LinuxMemory {
+  fn read_bytes(&self, addr: usize, buf: &mut [u8]) -> Result<(), TrainerError> {
+    let local = libc::iovec {
+      iov_base: buf.as_mut_ptr() as *mut c_void,
+      iov_len: buf.len(),
+    };
+    let remote = libc::iovec {
+      iov_base: addr as *mut c_void,
+      iov_len: buf.len(),
+    };
+    let bytes_read = unsafe { libc::process_vm_readv(self.pid, &local, 1, &remote, 1, 0) };
+    if bytes_read >= 0 && bytes_read as usize == buf.len() {
+      return Ok(());
+    }
+
+    // process_vm_readv 不可用（内核未开启、被 seccomp 拦截等）或者只读到一部分时，改用 /proc/<pid>/mem
+    match self.with_mem_file(|file| file.read_exact_at(buf, addr as u64)) {
+      Ok(()) => Ok(()),
+      Err(_) if bytes_read > 0 => {
+        Err(TrainerError::PartialRead { addr, expected: buf.len(), got: bytes_read as usize })
+      }
+      Err(err) => Err(self.map_error(err, addr)),
+    }
+  }
+
+  fn write_bytes(&self, addr: usize, data: &[u8]) -> Result<(), TrainerError> {
+    let local = libc::iovec {
+      iov_base: data.as_ptr() as *mut c_void,
+      iov_len: data.len(),
+    };
+    let remote = libc::iovec {
+      iov_base: addr as *mut c_void,
+      iov_len: data.len(),
+    };
+    let bytes_written = unsafe { libc::process_vm_writev(self.pid, &local, 1, &remote, 1, 0) };
+    if bytes_written >= 0 && bytes_written as usize == data.len() {
+      return Ok(());
+    }
+
+    // process_vm_writev 不能写只读页面，/proc/<pid>/mem 可以
+    match self.with_mem_file(|file| file.write_all_at(data, addr as u64)) {
+      Ok(()) => Ok(()),
+      Err(_) if bytes_written > 0 => {
+        Err(TrainerError::PartialWrite { addr, expected: data.len(), got: bytes_written as usize })
+      }
+      Err(err) => Err(self.map_error(err, addr)),
+    }
+  }
+
+  fn bitness(&self) -> Bitness {
+    self.bitness
+  }
+
+  /*
+    /proc/<pid>/maps 中的每个映射，映射的文件作为模块。
+    内核提供的 [vvar]、[vsyscall] 等特殊区域无法通过 process_vm_readv 读取，标记为不可读
+  */
+  fn regions(&self) -> Vec<MemoryRegion> {
+    let Ok(maps) = std::fs::read_to_string(format!("/proc/{}/maps", self.pid)) else {
+      return vec![];
+    };
+
+    maps.lines()
+      .filter_map(|line| {
+        // 00400000-00401000 r--p 00000000 08:01 1234   /path/to/age2_x1.exe
+        let mut parts = line.splitn(6, char::is_whitespace);
+        let (start, end) = parts.next()?.split_once('-')?;
+        let perms = parts.next()?.as_bytes();
+        let path = parts.nth(3).unwrap_or("").trim_start();
+        let special = matches!(path, "[vvar]" | "[vvar_vclock]" | "[vsyscall]");
+
+        let base = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        Some(MemoryRegion {
+          base,
+          size: end - base,
+          protection: Protection {
+            read: perms.first() == Some(&b'r') && !special,
+            write: perms.get(1) == Some(&b'w'),
+            execute: perms.get(2) == Some(&b'x'),
+            guard: false,
+          },
+          state: RegionState::Committed,
+          module: path.starts_with('/').then(|| path.rsplit('/').next().unwrap_or(path).to_string()),
+        })
+      })
+      .collect()
+  }
+
+  /*
+    从 /proc/<pid>/maps 中枚举映射的文件。Wine 把 exe 和 dll 直接映射到内存中，
+    同一个文件的所有映射合并为一个模块，模块名为文件名
+  */
+  fn modules(&self) -> Vec<ModuleInfo> {
+    let Ok(maps) = std::fs::read_to_string(format!("/proc/{}/maps", self.pid)) else {
+      return vec![];
+    };
+
+    let mut modules: Vec<(String, ModuleInfo)> = vec![];
+    for line in maps.lines() {
+      // 00400000-00401000 r--p 00000000 08:01 1234   /path/to/age2_x1.exe
+      let mut parts = line.splitn(6, char::is_whitespace);
+      let (Some(range), Some(path)) = (parts.next(), parts.nth(4)) else {
+        continue;
+      };
+      let path = path.trim_start();
+      if !path.starts_with('/') {
+        continue;
+      }
+      let Some((start, end)) = range.split_once('-') else {
+        continue;
+      };
+      let (Ok(start), Ok(end)) = (usize::from_str_radix(start, 16), usize::from_str_radix(end, 16)) else {
+        continue;
+      };
+
+      match modules.iter_mut().find(|(module_path, _)| module_path == path) {
+        Some((_, module)) => {
+          let module_end = (module.base + module.size).max(end);
+          module.base = module.base.min(start);
+          module.size = module_end - module.base;
+        }
+        None => {
+          let name = path.rsplit('/').next().unwrap_or(path).to_string();
+          modules.push((path.to_string(), ModuleInfo { name, base: start, size: end - start }));
+        }
+      }
+    }
+    modules.into_iter().map(|(_, module)| module).collect()
+  }
+
+  fn module_base(&self, name: &str) -> Option<usize> {
+    self.module_cache.find(name, || self.modules())
+  }
+}
+
+
+impl ProcessManager {
+  /*
+    扫描 /proc 列出系统中的进程。
+    Wine/Proton 中运行的程序，进程名使用 Windows 下的执行文件名（例如 age2_x1.exe），而不是 wine-preloader
+  */
+  pub fn get_procfs_process_list(&self) -> Vec<ProcessItem> {
+    let mut list: Vec<ProcessItem> = vec![];
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+      return list;
+    };
+
+    for entry in entries.flatten() {
+      let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+        continue;
+      };
+      if pid == 0 {
+        continue;
+      }
+
+      // 进程可能已经退出
+      let Some(name) = read_process_name(pid) else {
+        continue;
+      };
+
+      list.push(ProcessItem::new(pid, &name));
+    }
+
+    list.sort_by_key(|p| p.pid);
+    list
+  }
+}
+
+/*
+  读取进程名：
+  1. 命令行中有 .exe 文件（Wine 会把 argv[0] 改写为 Windows 路径），取其文件名；
+  2. 否则使用可执行文件的文件名；
+  3. 读不到可执行文件（内核线程、没有权限）时使用 comm。
+*/
+fn read_process_name(pid: u32) -> Option<String> {
+  let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+  let comm = comm.trim_end_matches('\n').to_string();
+
+  let exe_name = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+    .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()));
+
+  let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+  let is_wine = exe_name.as_deref().is_some_and(is_wine_loader) || comm.to_lowercase().ends_with(".exe");
+  if is_wine {
+    let image_name = cmdline.split(|&c| c == 0)
+      .map(String::from_utf8_lossy)
+      .find(|arg| arg.to_lowercase().ends_with(".exe"))
+      .map(|arg| windows_file_name(&arg).to_string());
+    if image_name.is_some() {
+      return image_name;
+    }
+  }
+
+  Some(exe_name.unwrap_or(comm))
+}
+
+/*
+  运行 Windows 程序的 wine 加载器。wineserver、winedbg 等其它 wine 程序不是游戏进程
+*/
+fn is_wine_loader(exe_name: &str) -> bool {
+  matches!(exe_name, "wine" | "wine64" | "wine-preloader" | "wine64-preloader")
+}
+
+/*
+  取 Windows 路径（C:\...\age2_x1.exe 或 Z:/home/.../age2_x1.exe）中的文件名
+*/
+fn windows_file_name(path: &str) -> &str {
+  path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_wine_loaders_only() {
+    for name in ["wine", "wine64", "wine-preloader", "wine64-preloader"] {
+      assert!(is_wine_loader(name), "{}", name);
+    }
+    for name in ["wineserver", "winedbg", "winedevice.exe", "winecfg"] {
+      assert!(!is_wine_loader(name), "{}", name);
+    }
+    assert_eq!(windows_file_name(r"C:\Program Files\age2_x1.exe"), "age2_x1.exe");
+    assert_eq!(windows_file_name("Z:/home/user/age2_x1.exe"), "age2_x1.exe");
+  }
+}
@@ -1,97 +1,640 @@
-use libloading;
+use std::collections::BTreeMap;
 use std::ffi::c_void;
+use std::fmt;
 use std::io;
 use std::mem::{size_of, MaybeUninit};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::error::TrainerError;
+
+const ERROR_ACCESS_DENIED: i32 = 5;
+
+
+/// 可以按字节直接读写的数据类型。
+///
+/// # Safety
+/// 实现该 trait 的类型必须满足任意字节序列都是合法值（不能包含 bool、引用、枚举等）。
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for usize {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for f32 {}
+unsafe impl Pod for f64 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
 
 
 /*
-  通过已经加载了的 windows kernel32.dll lib，调用 ReadProcessMemory 方法读取指定进程内存地址的数据
+  目标进程的位数，决定指针的长度
 */
-pub fn read_memory<T>(lib: &libloading::Library, process_handle: u32, addr: u32, size: u32) -> Result<T, Box<dyn std::error::Error>> {
-  unsafe {
-    type ReadProcessMemoryFn = unsafe extern "system" fn(
-      u32,              // handle
-      u32,              // address
-      *mut u32,         // buffer
-      usize,            // buffer size
-      *mut usize,       // bytes read
-    ) -> i32;
-
-    let read_size = size as usize;
-    let expected_size = size_of::<T>();
-    if expected_size == 0 {
-      return Err("read_memory requires a non zero-sized type".into());
-    }
-    if read_size != expected_size {
-      return Err(format!("size mismatch: expected {} bytes, got {}", expected_size, read_size).into());
-    }
-
-    let read_process_memory = lib.get::<ReadProcessMemoryFn>(b"ReadProcessMemory\0")?;
-
-    let mut result = MaybeUninit::<T>::uninit();
-    let mut bytes_read: usize = 0;
-    let success = read_process_memory(
-      process_handle,
-      addr,
-      result.as_mut_ptr() as *mut u32,
-      expected_size,
-      &mut bytes_read as *mut usize,
-    );
-
-    if success == 0 {
-      return Err(io::Error::last_os_error().into());
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitness {
+  Bits32,
+  Bits64,
+}
+
+impl Bitness {
+  pub fn pointer_size(self) -> usize {
+    match self {
+      Bitness::Bits32 => 4,
+      Bitness::Bits64 => 8,
+    }
+  }
+}
+
+
+/*
+  内存区域的访问权限
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Protection {
+  pub read: bool,
+  pub write: bool,
+  pub execute: bool,
+  // Windows 的保护页（PAGE_GUARD），第一次访问时会触发异常，扫描时必须跳过
+  pub guard: bool,
+}
+
+/*
+  按 rwx 的形式显示，保护页后面加上 g，例如 rw-g
+*/
+impl fmt::Display for Protection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}{}{}{}",
+      if self.read { 'r' } else { '-' },
+      if self.write { 'w' } else { '-' },
+      if self.execute { 'x' } else { '-' },
+      if self.guard { "g" } else { "" },
+    )
+  }
+}
+
+/*
+  内存区域的状态，Linux 下映射的区域都是 Committed
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionState {
+  // 已分配物理内存，可以访问
+  Committed,
+  // 只保留了地址空间
+  Reserved,
+  // 未使用
+  Free,
+}
+
+/*
+  进程地址空间中的一段内存
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+  pub base: usize,
+  pub size: usize,
+  pub protection: Protection,
+  pub state: RegionState,
+  // 映射该区域的模块（执行文件、dll 等），匿名内存为 None
+  pub module: Option<String>,
+}
+
+impl MemoryRegion {
+  pub fn end(&self) -> usize {
+    self.base + self.size
+  }
+
+  /*
+    是否可以安全地读取：已提交、可读并且不是保护页
+  */
+  pub fn is_readable(&self) -> bool {
+    self.state == RegionState::Committed && self.protection.read && !self.protection.guard
+  }
+}
+
+
+/*
+  进程中加载的模块（执行文件、dll）
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleInfo {
+  pub name: String,
+  pub base: usize,
+  pub size: usize,
+}
+
+/*
+  模块列表的缓存：连接进程后第一次查找时枚举模块，
+  找不到时重新枚举一次（dll 可能在连接之后才加载）
+*/
+#[derive(Debug, Default)]
+pub(crate) struct ModuleCache {
+  modules: Mutex<Vec<ModuleInfo>>,
+}
+
+impl ModuleCache {
+  pub(crate) fn find(&self, name: &str, load: impl FnOnce() -> Vec<ModuleInfo>) -> Option<usize> {
+    let mut modules = self.modules.lock().unwrap();
+    if let Some(module) = find_module(&modules, name) {
+      return Some(module.base);
+    }
+    *modules = load();
+    find_module(&modules, name).map(|module| module.base)
+  }
+}
+
+fn find_module<'a>(modules: &'a [ModuleInfo], name: &str) -> Option<&'a ModuleInfo> {
+  modules.iter().find(|module| module.name.eq_ignore_ascii_case(name))
+}
+
+/*
+  包含地址 addr 的模块名
+*/
+pub(crate) fn module_at(modules: &[ModuleInfo], addr: usize) -> Option<String> {
+  modules.iter()
+    .find(|module| addr >= module.base && addr < module.base + module.size)
+    .map(|module| module.name.clone())
+}
+
+
+/*
+  目标进程内存的读写后端。
+  Windows 下使用 kernel32 的 ReadProcessMemory/WriteProcessMemory，其它环境可以提供自己的实现。
+  后端会在后台任务中使用，必须可以跨线程共享。
+*/
+pub trait ProcessMemory: Send + Sync {
+  fn read_bytes(&self, addr: usize, buf: &mut [u8]) -> Result<(), TrainerError>;
+  fn write_bytes(&self, addr: usize, data: &[u8]) -> Result<(), TrainerError>;
+
+  /*
+    目标进程的位数，在连接进程时检测
+  */
+  fn bitness(&self) -> Bitness;
+
+  /*
+    进程的内存区域，按地址排序，不支持时返回空列表
+  */
+  fn regions(&self) -> Vec<MemoryRegion> {
+    vec![]
+  }
+
+  /*
+    所有可以安全读取的内存区域，用于扫描内存
+  */
+  fn readable_regions(&self) -> Vec<Range<usize>> {
+    self.regions().iter()
+      .filter(|region| region.is_readable())
+      .map(|region| region.base..region.end())
+      .collect()
+  }
+
+  /*
+    枚举进程中加载的模块，不支持时返回空列表
+  */
+  fn modules(&self) -> Vec<ModuleInfo> {
+    vec![]
+  }
+
+  /*
+    模块的加载基址，模块名不区分大小写，找不到时返回 None
+  */
+  fn module_base(&self, name: &str) -> Option<usize> {
+    find_module(&self.modules(), name).map(|module| module.base)
+  }
+}
+
+/*
+  在 ProcessMemory 之上提供按类型读写的辅助方法
+*/
+pub trait ProcessMemoryExt: ProcessMemory {
+  fn read<T: Pod>(&self, addr: usize) -> Result<T, TrainerError> {
+    let size = size_of::<T>();
+    if size == 0 {
+      return Err(TrainerError::SizeMismatch { expected: 1, got: 0 });
+    }
+
+    let mut result = MaybeUninit::<T>::zeroed();
+    // SAFETY: result 已经清零，且 T: Pod 保证任意字节序列都是合法值
+    let buf = unsafe { std::slice::from_raw_parts_mut(result.as_mut_ptr() as *mut u8, size) };
+    self.read_bytes(addr, buf)?;
+    Ok(unsafe { result.assume_init() })
+  }
+
+  fn write<T: Pod>(&self, addr: usize, value: T) -> Result<(), TrainerError> {
+    let size = size_of::<T>();
+    if size == 0 {
+      return Err(TrainerError::SizeMismatch { expected: 1, got: 0 });
+    }
+
+    // SAFETY: T: Pod 没有填充字节以外的约束，按字节查看是安全的
+    let buf = unsafe { std::slice::from_raw_parts(&value as *const T as *const u8, size) };
+    self.write_bytes(addr, buf)
+  }
+
+  /*
+    按目标进程的位数读取 4 或 8 字节的指针
+  */
+  fn read_pointer(&self, addr: usize) -> Result<usize, TrainerError> {
+    match self.bitness() {
+      Bitness::Bits32 => Ok(self.read::<u32>(addr)? as usize),
+      Bitness::Bits64 => Ok(self.read::<u64>(addr)? as usize),
+    }
+  }
+}
+
+impl<M: ProcessMemory + ?Sized> ProcessMemoryExt for M {}
+
+
+/*
+  通过已经加载了的 windows kernel32.dll lib，调用 ReadProcessMemory/WriteProcessMemory 方法读写指定进程内存地址的数据
+*/
+pub struct Kernel32Memory {
+  lib: Arc<libloading::Library>,
+  handle: usize,
+  bitness: Bitness,
+  module_cache: ModuleCache,
+}
+
+impl Kernel32Memory {
+  /*
+    进程句柄交给 Kernel32Memory，被 drop 时关闭，创建失败时同样关闭
+  */
+  pub fn new(lib: Arc<libloading::Library>, handle: usize) -> Result<Self, TrainerError> {
+    let mut memory = Self { lib, handle, bitness: Bitness::Bits32, module_cache: ModuleCache::default() };
+    memory.bitness = detect_bitness(&memory.lib, handle)?;
+    Ok(memory)
+  }
+
+  /*
+    通过 K32EnumProcessModulesEx 枚举模块（Windows 7 起 kernel32 中提供，不需要 psapi）
+  */
+  fn enum_modules(&self) -> Result<Vec<ModuleInfo>, TrainerError> {
+    const LIST_MODULES_ALL: u32 = 0x03;
+    const MAX_MODULES: usize = 1024;
+
+    #[repr(C)]
+    struct RawModuleInfo {
+      base: usize,
+      size: u32,
+      entry_point: usize,
     }
-    if bytes_read != expected_size {
-      return Err(format!("ReadProcessMemory copied {} bytes, expected {}", bytes_read, expected_size).into());
+
+    unsafe {
+      type EnumProcessModulesExFn = unsafe extern "system" fn(usize, *mut usize, u32, *mut u32, u32) -> i32;
+      type GetModuleBaseNameAFn = unsafe extern "system" fn(usize, usize, *mut u8, u32) -> u32;
+      type GetModuleInformationFn = unsafe extern "system" fn(usize, usize, *mut RawModuleInfo, u32) -> i32;
+
+      let enum_process_modules = self.lib.get::<EnumProcessModulesExFn>(b"K32EnumProcessModulesEx\0")?;
+      let get_module_base_name = self.lib.get::<GetModuleBaseNameAFn>(b"K32GetModuleBaseNameA\0")?;
+      let get_module_information = self.lib.get::<GetModuleInformationFn>(b"K32GetModuleInformation\0")?;
+
+      let mut handles = vec![0usize; MAX_MODULES];
+      let mut bytes_needed: u32 = 0;
+      let success = enum_process_modules(
+        self.handle,
+        handles.as_mut_ptr(),
+        (MAX_MODULES * size_of::<usize>()) as u32,
+        &mut bytes_needed as *mut u32,
+        LIST_MODULES_ALL,
+      );
+      if success == 0 {
+        return Err(io::Error::last_os_error().into());
+      }
+      let count = (bytes_needed as usize / size_of::<usize>()).min(MAX_MODULES);
+
+      let mut modules = vec![];
+      for &module in &handles[..count] {
+        let mut name_buf = [0u8; 260];
+        let len = get_module_base_name(self.handle, module, name_buf.as_mut_ptr(), name_buf.len() as u32);
+        let mut info = RawModuleInfo { base: 0, size: 0, entry_point: 0 };
+        if len == 0 || get_module_information(self.handle, module, &mut info, size_of::<RawModuleInfo>() as u32) == 0 {
+          continue;
+        }
+        modules.push(ModuleInfo {
+          name: String::from_utf8_lossy(&name_buf[..len as usize]).to_string(),
+          base: info.base,
+          size: info.size as usize,
+        });
+      }
+      Ok(modules)
     }
+  }
+}
 
-    Ok(result.assume_init())
+impl Drop for Kernel32Memory {
+  fn drop(&mut self) {
+    unsafe {
+      if let Ok(close_handle) = self.lib.get::<unsafe extern "system" fn(usize) -> i32>(b"CloseHandle\0") {
+        close_handle(self.handle);
+      }
+    }
   }
 }
 
 /*
-  通过已经加载了的 windows kernel32.dll lib，调用 WriteProcessMemory 方法写入指定进程内存地址的数据
+  通过 IsWow64Process 判断目标进程的位数：
+  64 位的修改器中，WOW64 进程是 32 位，其它进程是 64 位；
+  32 位的修改器中，只有自身运行在 WOW64 下而目标进程不是时，目标进程才是 64 位
 */
-pub fn write_memory<T>(lib: &libloading::Library, process_handle: u32, addr: u32, data: T, size: u32) -> Result<(), Box<dyn std::error::Error>> {
+fn detect_bitness(lib: &libloading::Library, handle: usize) -> Result<Bitness, TrainerError> {
   unsafe {
-    type WriteProcessMemoryFn = unsafe extern "system" fn(
-      u32,              // process handle
-      u32,              // address
-      u32,              // buffer
-      usize,            // buffer size
-      *mut usize,       // bytes written
-    ) -> i32;
-
-    let write_size = size as usize;
-    let expected_size = size_of::<T>();
-    if expected_size == 0 {
-      return Err("write_memory requires a non zero-sized type".into());
-    }
-    if write_size != expected_size {
-      return Err(format!("size mismatch: expected {} bytes, got {}", expected_size, write_size).into());
-    }
-
-    let write_process_memory = lib.get::<WriteProcessMemoryFn>(b"WriteProcessMemory\0")?;
-
-    // let data_buf = MaybeUninit::new(data);               // method 1: 获取指针
-    let data_addr = &data as *const T;            // method 2: 获取指针
-    let mut bytes_written: usize = 0;
-    let success = write_process_memory(
-      process_handle,
-      addr,
-      // data_buf.as_ptr() as u32,      // method 1
-      data_addr as u32,                 // method 2
-      expected_size,
-      &mut bytes_written as *mut usize,
-    );
-
-    if success == 0 {
+    type IsWow64ProcessFn = unsafe extern "system" fn(usize, *mut i32) -> i32;
+    type GetCurrentProcessFn = unsafe extern "system" fn() -> usize;
+
+    let is_wow64_process = lib.get::<IsWow64ProcessFn>(b"IsWow64Process\0")?;
+    let get_current_process = lib.get::<GetCurrentProcessFn>(b"GetCurrentProcess\0")?;
+
+    let mut target_wow64: i32 = 0;
+    if is_wow64_process(handle, &mut target_wow64 as *mut i32) == 0 {
       return Err(io::Error::last_os_error().into());
     }
-    if bytes_written != expected_size {
-      return Err(format!("WriteProcessMemory wrote {} bytes, expected {}", bytes_written, expected_size).into());
+
+    if cfg!(target_pointer_width = "64") {
+      return Ok(if target_wow64 != 0 { Bitness::Bits32 } else { Bitness::Bits64 });
+    }
+
+    let mut self_wow64: i32 = 0;
+    if is_wow64_process(get_current_process(), &mut self_wow64 as *mut i32) == 0 {
+      return Err(io::Error::last_os_error().into());
+    }
+    Ok(if self_wow64 != 0 && target_wow64 == 0 { Bitness::Bits64 } else { Bitness::Bits32 })
+  }
+}
+
+impl ProcessMemory for Kernel32Memory {
+  fn read_bytes(&self, addr: usize, buf: &mut [u8]) -> Result<(), TrainerError> {
+    unsafe {
+      type ReadProcessMemoryFn = unsafe extern "system" fn(
+        usize,            // handle
+        usize,            // address
+        *mut c_void,      // buffer
+        usize,            // buffer size
+        *mut usize,       // bytes read
+      ) -> i32;
+
+      let read_process_memory = self.lib.get::<ReadProcessMemoryFn>(b"ReadProcessMemory\0")?;
+
+      let mut bytes_read: usize = 0;
+      let success = read_process_memory(
+        self.handle,
+        addr,
+        buf.as_mut_ptr() as *mut c_void,
+        buf.len(),
+        &mut bytes_read as *mut usize,
+      );
+
+      if success == 0 && bytes_read == 0 {
+        return Err(win32_error(addr));
+      }
+      if bytes_read != buf.len() {
+        return Err(TrainerError::PartialRead { addr, expected: buf.len(), got: bytes_read });
+      }
+
+      Ok(())
+    }
+  }
+
+  fn write_bytes(&self, addr: usize, data: &[u8]) -> Result<(), TrainerError> {
+    unsafe {
+      type WriteProcessMemoryFn = unsafe extern "system" fn(
+        usize,            // process handle
+        usize,            // address
+        *const c_void,    // buffer
+        usize,            // buffer size
+        *mut usize,       // bytes written
+      ) -> i32;
+
+      let write_process_memory = self.lib.get::<WriteProcessMemoryFn>(b"WriteProcessMemory\0")?;
+
+      let mut bytes_written: usize = 0;
+      let success = write_process_memory(
+        self.handle,
+        addr,
+        data.as_ptr() as *const c_void,
+        data.len(),
+        &mut bytes_written as *mut usize,
+      );
+
+      if success == 0 && bytes_written == 0 {
+        return Err(win32_error(addr));
+      }
+      if bytes_written != data.len() {
+        return Err(TrainerError::PartialWrite { addr, expected: data.len(), got: bytes_written });
+      }
+
+      Ok(())
+    }
+  }
+
+  fn bitness(&self) -> Bitness {
+    self.bitness
+  }
+
+  /*
+    通过 VirtualQueryEx 遍历整个地址空间
+  */
+  fn regions(&self) -> Vec<MemoryRegion> {
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const PAGE_READONLY: u32 = 0x02;
+    const PAGE_READWRITE: u32 = 0x04;
+    const PAGE_WRITECOPY: u32 = 0x08;
+    const PAGE_EXECUTE: u32 = 0x10;
+    const PAGE_EXECUTE_READ: u32 = 0x20;
+    const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+    const PAGE_EXECUTE_WRITECOPY: u32 = 0x80;
+    const PAGE_GUARD: u32 = 0x100;
+
+    #[repr(C)]
+    struct MemoryBasicInformation {
+      base_address: usize,
+      allocation_base: usize,
+      allocation_protect: u32,
+      #[cfg(target_pointer_width = "64")]
+      partition_id: u16,
+      region_size: usize,
+      state: u32,
+      protect: u32,
+      region_type: u32,
     }
 
+    let modules = self.modules();
+    let mut regions = vec![];
+    unsafe {
+      type VirtualQueryExFn = unsafe extern "system" fn(usize, usize, *mut MemoryBasicInformation, usize) -> usize;
+      let Ok(virtual_query) = self.lib.get::<VirtualQueryExFn>(b"VirtualQueryEx\0") else {
+        return regions;
+      };
+
+      let mut addr: usize = 0;
+      loop {
+        let mut info = MaybeUninit::<MemoryBasicInformation>::zeroed();
+        if virtual_query(self.handle, addr, info.as_mut_ptr(), size_of::<MemoryBasicInformation>()) == 0 {
+          break;
+        }
+        let info = info.assume_init();
+        let protect = info.protect & 0xFF;
+        regions.push(MemoryRegion {
+          base: info.base_address,
+          size: info.region_size,
+          protection: Protection {
+            read: matches!(protect, PAGE_READONLY | PAGE_READWRITE | PAGE_WRITECOPY
+              | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY),
+            write: matches!(protect, PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY),
+            execute: matches!(protect, PAGE_EXECUTE | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY),
+            guard: info.protect & PAGE_GUARD != 0,
+          },
+          state: match info.state {
+            MEM_COMMIT => RegionState::Committed,
+            MEM_RESERVE => RegionState::Reserved,
+            _ => RegionState::Free,
+          },
+          module: module_at(&modules, info.base_address),
+        });
+        match info.base_address.checked_add(info.region_size) {
+          Some(next) if next > addr => addr = next,
+          _ => break,
+        }
+      }
+    }
+    regions
+  }
+
+  fn modules(&self) -> Vec<ModuleInfo> {
+    self.enum_modules().unwrap_or_default()
+  }
+
+  fn module_base(&self, name: &str) -> Option<usize> {
+    self.module_cache.find(name, || self.modules())
+  }
+}
+
+/*
+  把 ReadProcessMemory/WriteProcessMemory 失败时的系统错误转换为 TrainerError
+*/
+fn win32_error(addr: usize) -> TrainerError {
+  const ERROR_PARTIAL_COPY: i32 = 299;
+  const ERROR_NOACCESS: i32 = 998;
+
+  let err = io::Error::last_os_error();
+  match err.raw_os_error() {
+    Some(ERROR_ACCESS_DENIED) => TrainerError::AccessDenied(ERROR_ACCESS_DENIED),
+    Some(ERROR_PARTIAL_COPY) | Some(ERROR_NOACCESS) => TrainerError::InvalidAddress(addr),
+    _ => TrainerError::Io(err),
+  }
+}
+
+
+/*
+  基于内存缓冲区的后端，不依赖任何系统接口。
+  可以把若干段数据映射到指定地址，用来在没有 Windows 的环境下调试修改器逻辑。
+*/
+#[derive(Debug)]
+pub struct BufferMemory {
+  regions: Mutex<BTreeMap<usize, Vec<u8>>>,
+  modules: Mutex<Vec<ModuleInfo>>,
+  bitness: Bitness,
+}
+
+impl Default for BufferMemory {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl BufferMemory {
+  /*
+    模拟 32 位的游戏进程
+  */
+  pub fn new() -> Self {
+    Self::with_bitness(Bitness::Bits32)
+  }
+
+  pub fn with_bitness(bitness: Bitness) -> Self {
+    Self {
+      regions: Mutex::new(BTreeMap::new()),
+      modules: Mutex::new(vec![]),
+      bitness,
+    }
+  }
+
+  /*
+    把一段数据映射到 base 地址，已存在的同一基址的数据会被覆盖
+  */
+  pub fn map(&self, base: usize, data: Vec<u8>) {
+    self.regions.lock().unwrap().insert(base, data);
+  }
+
+  /*
+    登记一个模块的加载基址，模块的大小取映射在 base 地址的数据长度
+  */
+  pub fn add_module(&self, name: &str, base: usize) {
+    let size = self.regions.lock().unwrap().get(&base).map_or(0, Vec::len);
+    self.modules.lock().unwrap().push(ModuleInfo { name: name.to_string(), base, size });
+  }
+}
+
+impl ProcessMemory for BufferMemory {
+  fn read_bytes(&self, addr: usize, buf: &mut [u8]) -> Result<(), TrainerError> {
+    let regions = self.regions.lock().unwrap();
+    let (base, data) = regions.range(..=addr).next_back()
+      .ok_or(TrainerError::InvalidAddress(addr))?;
+
+    let start = addr - base;
+    if start >= data.len() {
+      return Err(TrainerError::InvalidAddress(addr));
+    }
+    let end = start + buf.len();
+    if end > data.len() {
+      return Err(TrainerError::PartialRead { addr, expected: buf.len(), got: data.len() - start });
+    }
+    buf.copy_from_slice(&data[start..end]);
     Ok(())
   }
+
+  fn write_bytes(&self, addr: usize, data: &[u8]) -> Result<(), TrainerError> {
+    let mut regions = self.regions.lock().unwrap();
+    let (base, region) = regions.range_mut(..=addr).next_back()
+      .ok_or(TrainerError::InvalidAddress(addr))?;
+
+    let start = addr - *base;
+    if start >= region.len() {
+      return Err(TrainerError::InvalidAddress(addr));
+    }
+    let end = start + data.len();
+    if end > region.len() {
+      return Err(TrainerError::PartialWrite { addr, expected: data.len(), got: region.len() - start });
+    }
+    region[start..end].copy_from_slice(data);
+    Ok(())
+  }
+
+  fn bitness(&self) -> Bitness {
+    self.bitness
+  }
+
+  /*
+    映射的每段数据都是可读写的内存
+  */
+  fn regions(&self) -> Vec<MemoryRegion> {
+    let modules = self.modules();
+    self.regions.lock().unwrap().iter()
+      .map(|(base, data)| MemoryRegion {
+        base: *base,
+        size: data.len(),
+        protection: Protection { read: true, write: true, execute: false, guard: false },
+        state: RegionState::Committed,
+        module: module_at(&modules, *base),
+      })
+      .collect()
+  }
+
+  fn modules(&self) -> Vec<ModuleInfo> {
+    self.modules.lock().unwrap().clone()
+  }
 }
@@ -1,252 +1,373 @@
 pub mod process;
 pub mod memory;
-
-use libloading;
-
-/*
-四项资源(float)：
-0x_______0:
-食物(4Bytes)            木材(4Bytes)                石头(4Bytes)                黄金(4Bytes)
-人口上限差值(4Bytes)    xx                          xx                          xx
-xx                      xx                          xx                          当前人口数(4Bytes)
-*/
-
-const BASE_FOOD_ADDR: u32 = 0x007A5FEC;     // 食物的基址
-
-
-#[derive(Debug, Default)]
-pub struct GameInfo {
-  pub pid: u32,
-  pub is_running: bool,
-  pub food: f32,
-  pub wood: f32,
-  pub stone: f32,
-  pub gold: f32,
-  pub leave_population: f32,
-  pub current_population: f32,
-}
+pub mod pointer;
+#[cfg(target_os = "linux")]
+pub mod linux;
+
+use std::sync::Arc;
+use memory::ProcessMemory;
+use crate::config::{CheatDef, Config};
+use crate::error::TrainerError;
+use crate::game::{self, GameInfo};
+use crate::layout::Layout;
+use crate::profile::{self, Fingerprint, Profiles};
+use crate::cheat;
+use crate::unit::{self, UnitInfo};
 
 pub struct WinApi {
-  pub psapi_lib: libloading::Library,
-  pub kernel32_lib: libloading::Library,
+  // 只有 Windows 下才会加载系统的 dll
+  pub psapi_lib: Option<libloading::Library>,
+  pub kernel32_lib: Option<Arc<libloading::Library>>,
   pub game_process: Option<process::ProcessItem>,
+  pub profiles: Profiles,
+  // 每个字段要写入的值
+  pub config: Config,
+  // 当前游戏版本使用的内存布局，没有识别出游戏版本时为 None
+  pub layout: Option<Layout>,
+  memory: Option<Arc<dyn ProcessMemory>>,
+  // 没有识别出游戏版本时，游戏执行文件的特征
+  unknown_version: Option<String>,
+  // 识别出了版本但是特征码解析失败（例如游戏还在加载），下次读取时重新识别
+  retry_detect: bool,
+  // 连续出现暂时性错误的次数
+  failures: u32,
 }
 
 impl WinApi {
-  pub fn new() -> Self {
-    let psapi_lib= load_psapi_library();
-    let kernel32_lib = load_kernel32_library();
+  // 连续出现暂时性错误超过该次数后，认为游戏进程已经退出
+  const MAX_RETRIES: u32 = 30;
+
+  /*
+    加载系统的 dll、版本配置和修改配置，任何一项失败时返回错误，不会 panic
+  */
+  pub fn try_new() -> Result<Self, TrainerError> {
+    let psapi_lib = cfg!(windows).then(load_psapi_library).transpose()?;
+    let kernel32_lib = cfg!(windows).then(load_kernel32_library).transpose()?.map(Arc::new);
+    let profiles = Profiles::load_default()?;
+    let config = Config::load_default()?;
+    for layout in profiles.layouts() {
+      config.validate_layout(layout)?;
+    }
 
-    Self {
+    Ok(Self {
       psapi_lib,
       kernel32_lib,
       game_process: None,
+      profiles,
+      config,
+      layout: None,
+      memory: None,
+      unknown_version: None,
+      retry_detect: false,
+      failures: 0,
+    })
+  }
+
+  /*
+    列出系统中的进程：Windows 下使用 psapi.dll，Linux 下扫描 /proc
+  */
+  pub fn get_process_list(&self, manager: &process::ProcessManager) -> Vec<process::ProcessItem> {
+    if let (Some(psapi_lib), Some(kernel32_lib)) = (&self.psapi_lib, &self.kernel32_lib) {
+      return manager.get_process_list(psapi_lib, kernel32_lib);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+      manager.get_procfs_process_list()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+      vec![]
     }
   }
 
-  pub fn set_game_process(&mut self, p: process::ProcessItem) -> Result<(), Box<dyn std::error::Error>> {
-    let mut p = p.clone();
-    p.inject(&self.kernel32_lib)?;
-    self.game_process = Some(p);
+  /*
+    查找游戏进程：执行文件名为 age2*.exe，或者是某个版本配置中的执行文件
+  */
+  pub fn find_game_process(&self, manager: &process::ProcessManager) -> Option<process::ProcessItem> {
+    let list = self.get_process_list(manager);
+    manager.find_game_process_in(list, |name| self.profiles.has_image_name(name))
+  }
+
+  /*
+    检查修改配置是否可以用于所有的版本配置，例如开关类修改只能使用 set
+  */
+  pub fn check_cheat(&self, cheat: &CheatDef) -> Result<(), TrainerError> {
+    for toggle in self.profiles.layouts().filter_map(|layout| layout.toggle(&cheat.field)) {
+      cheat.check_toggle(toggle)?;
+    }
     Ok(())
   }
 
-  pub fn read_game_info(&mut self) -> Result<GameInfo, Box<dyn std::error::Error>> {
-    let mut game_info = GameInfo::default();
+  pub fn set_game_process(&mut self, p: process::ProcessItem) -> Result<(), TrainerError> {
+    let mut p = p.clone();
+    let memory = self.open_memory(&mut p)?;
+    self.attach_memory(p, memory);
+    Ok(())
+  }
 
-    if self.game_process.is_none() {
-      return Ok(game_info);
+  /*
+    根据当前平台选择内存后端：Windows 下使用 kernel32，Linux 下直接读写 Wine/Proton 中的游戏进程
+  */
+  fn open_memory(&self, p: &mut process::ProcessItem) -> Result<Box<dyn ProcessMemory>, TrainerError> {
+    if let Some(kernel32_lib) = &self.kernel32_lib {
+      p.inject(kernel32_lib)?;
+      // 句柄由内存后端关闭：断开连接之后锁定引擎可能还在使用它，最后一个引用释放时才关闭
+      let handle = std::mem::take(&mut p.handle);
+      return Ok(Box::new(memory::Kernel32Memory::new(kernel32_lib.clone(), handle)?));
     }
 
-    // 读取游戏内存数据失败
-    let mut info = &mut game_info;
-    if let Err(e) = self._read_game_value(info) {
-      self.game_process = None;
-      return Err(e);
+    #[cfg(target_os = "linux")]
+    {
+      Ok(Box::new(linux::LinuxMemory::open(p.pid, &p.name)?))
     }
+    #[cfg(not(target_os = "linux"))]
+    {
+      Err(TrainerError::LibraryLoad("kernel32.dll is not loaded".to_string()))
+    }
+  }
 
-    game_info.pid = self.game_process.as_ref().unwrap().pid;
+  /*
+    使用指定的内存后端读写游戏进程，并根据执行文件识别游戏版本
+  */
+  pub fn attach_memory(&mut self, p: process::ProcessItem, memory: Box<dyn ProcessMemory>) {
+    self.update_layout(memory.as_ref(), &p.name);
+    self.game_process = Some(p);
+    self.memory = Some(Arc::from(memory));
+    self.failures = 0;
+  }
 
-    return Ok(game_info);
+  /*
+    识别游戏版本并解析特征码。不是未知版本的错误（例如游戏还没有加载完代码，特征码找不到）
+    可能是暂时性的，记录下来，读取游戏数据时重试
+  */
+  fn update_layout(&mut self, memory: &dyn ProcessMemory, image_name: &str) {
+    match self.detect_layout(memory, image_name) {
+      Ok(layout) => {
+        self.layout = Some(layout);
+        self.unknown_version = None;
+        self.retry_detect = false;
+      }
+      Err(e) => {
+        self.layout = None;
+        self.retry_detect = !matches!(e, TrainerError::UnknownVersion(_));
+        self.unknown_version = Some(match e {
+          TrainerError::UnknownVersion(fingerprint) => fingerprint,
+          e => format!("{}: {}", image_name, e),
+        });
+      }
+    }
   }
-  fn _read_game_value(&mut self, info: &mut GameInfo) -> Result<(), Box<dyn std::error::Error>> {
-    let mut value = memory::read_memory::<u32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      BASE_FOOD_ADDR,
-      4
-    )?;
 
-    if value == 0 {
-      // 游戏程序已运行，但是还未进入游戏状态
-      return Ok(());
+  fn detect_layout(&self, memory: &dyn ProcessMemory, image_name: &str) -> Result<Layout, TrainerError> {
+    // 使用 layout.toml 时不需要识别版本
+    if let Some(layout) = &self.profiles.forced {
+      let mut layout = layout.clone();
+      layout.resolve_signatures(memory)?;
+      return Ok(layout);
     }
 
-    let food_pointer = value + 0xA8;    // 当前食物地址指针 = 基址 + 偏移地址
-    let food_addr = memory::read_memory::<u32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_pointer,
-      4
-    )?;
+    let fingerprint = profile::read_fingerprint(memory, image_name)?;
+    let mut layout = match self.profiles.detect(&fingerprint) {
+      Some(layout) => layout.clone(),
+      None => return Err(TrainerError::UnknownVersion(fingerprint.to_string())),
+    };
+    layout.resolve_signatures(memory)?;
+    Ok(layout)
+  }
 
-    if food_addr == 0 {
-      // 游戏程序已运行，但是还未进入游戏状态
-      return Ok(());
-    }
+  /*
+    读取已连接的游戏执行文件的特征，用于编写新的版本配置
+  */
+  pub fn fingerprint(&self) -> Result<Fingerprint, TrainerError> {
+    let (Some(p), Some(memory)) = (&self.game_process, &self.memory) else {
+      return Err(TrainerError::ProcessNotFound(0));
+    };
+    profile::read_fingerprint(memory.as_ref(), &p.name)
+  }
 
-    info.is_running = true;
-    // println!("Base food addr value: 0x{:X}", food_addr);
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 0,
-      4
-    )?;
-    info.food = value;
-
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 4,
-      4
-    )?;
-    info.wood = value;
-
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 8,
-      4
-    )?;
-    info.stone = value;
-
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 12,
-      4
-    )?;
-    info.gold = value;
-
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 16,
-      4
-    )?;
-    info.leave_population = value;
-
-    let mut value = memory::read_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 44,
-      4
-    )?;
-    info.current_population = value;
+  /*
+    已连接的游戏进程的内存后端，用于扫描等直接读写内存的功能
+  */
+  pub fn memory(&self) -> Option<&dyn ProcessMemory> {
+    self.memory.as_deref()
+  }
 
-    Ok(())
+  /*
+    与后台任务共享的内存后端
+  */
+  pub fn shared_memory(&self) -> Option<Arc<dyn ProcessMemory>> {
+    self.memory.clone()
   }
 
-  pub fn write_game_info(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    if self.game_process.is_none() {
-      return Ok(());
+  /*
+    已连接的游戏进程的位数
+  */
+  pub fn bitness(&self) -> Option<memory::Bitness> {
+    self.memory.as_ref().map(|memory| memory.bitness())
+  }
+
+  /*
+    已连接的游戏进程的内存区域
+  */
+  pub fn regions(&self) -> Vec<memory::MemoryRegion> {
+    self.memory.as_ref().map(|memory| memory.regions()).unwrap_or_default()
+  }
+
+  /*
+    已连接的游戏进程中加载的模块
+  */
+  pub fn modules(&self) -> Vec<memory::ModuleInfo> {
+    self.memory.as_ref().map(|memory| memory.modules()).unwrap_or_default()
+  }
+
+  pub fn detach(&mut self) {
+    self.game_process = None;
+    self.memory = None;
+    self.layout = None;
+    self.unknown_version = None;
+    self.retry_detect = false;
+    self.failures = 0;
+  }
+
+  /*
+    暂时性的错误（例如游戏正在加载时只读到一部分数据）保留进程，下次重试；
+    其它错误或者重试次数过多时断开与游戏进程的连接
+  */
+  fn handle_error(&mut self, e: TrainerError) -> TrainerError {
+    self.failures += 1;
+    if !e.is_retryable() || self.failures > Self::MAX_RETRIES {
+      self.detach();
     }
+    e
+  }
 
-    // 写入游戏内存数据失败
-    if let Err(e) = self._write_game_value() {
-      self.game_process = None;
-      return Err(e);
+  pub fn read_game_info(&mut self) -> Result<GameInfo, TrainerError> {
+    let mut game_info = GameInfo::default();
+
+    if self.layout.is_none() && self.retry_detect
+      && let (Some(p), Some(memory)) = (&self.game_process, &self.memory) {
+      // 重试次数过多时不再重试，保留最后的错误
+      let (memory, image_name) = (memory.clone(), p.name.clone());
+      self.update_layout(memory.as_ref(), &image_name);
+      self.failures += 1;
+      if self.layout.is_some() {
+        self.failures = 0;
+      } else if self.failures > Self::MAX_RETRIES {
+        self.retry_detect = false;
+      }
     }
 
-    return Ok(());
-  }
-  fn _write_game_value(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    let mut value = memory::read_memory::<u32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      BASE_FOOD_ADDR,
-      4
-    )?;
-    if value == 0 {
-      // 游戏程序已运行，但是还未进入游戏状态
-      return Ok(());
+    let (Some(p), Some(memory)) = (&self.game_process, &self.memory) else {
+      return Ok(game_info);
+    };
+    let pid = p.pid;
+    let Some(layout) = &self.layout else {
+      // 不支持的游戏版本，不读取任何数据
+      return Err(TrainerError::UnknownVersion(self.unknown_version.clone().unwrap_or_default()));
+    };
+
+    // 读取游戏内存数据失败
+    if let Err(e) = game::read_game_value(memory.as_ref(), layout, &mut game_info) {
+      return Err(self.handle_error(e));
     }
 
-    let food_pointer = value + 0xA8;    // 当前食物地址指针 = 基址 + 偏移地址
-    let food_addr = memory::read_memory::<u32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_pointer,
-      4
-    )?;
+    self.failures = 0;
+    game_info.pid = pid;
 
-    if food_addr == 0 {
+    Ok(game_info)
+  }
+
+  /*
+    读取玩家的所有单位，player 为 None 时为本地玩家。没有连接游戏进程时返回空列表
+  */
+  pub fn read_units(&mut self, player: Option<usize>) -> Result<Vec<UnitInfo>, TrainerError> {
+    let (Some(memory), Some(layout)) = (&self.memory, &self.layout) else {
+      return Ok(vec![]);
+    };
+
+    match unit::read_units(memory.as_ref(), layout, player) {
+      Ok(units) => Ok(units),
       // 游戏程序已运行，但是还未进入游戏状态
+      Err(TrainerError::NullPointer { .. }) => Ok(vec![]),
+      // 布局中没有单位列表，不影响与游戏进程的连接
+      Err(e @ TrainerError::Layout(_)) => Err(e),
+      Err(e) => Err(self.handle_error(e)),
+    }
+  }
+
+  pub fn write_game_info(&mut self) -> Result<(), TrainerError> {
+    let (Some(memory), Some(layout)) = (&self.memory, &self.layout) else {
       return Ok(());
+    };
+
+    // 写入游戏内存数据失败
+    if let Err(e) = cheat::write_game_value(memory.as_ref(), layout, &self.config.cheats) {
+      return Err(self.handle_error(e));
     }
 
-    // println!("Write 99999.0 to food pointer:");
-    let new_value: f32 = 99999.0;
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 0,
-      new_value.clone(),
-      4,
-    )?;
-
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 4,
-      new_value.clone(),
-      4,
-    )?;
-
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 8,
-      new_value.clone(),
-      4,
-    )?;
-
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 12,
-      new_value.clone(),
-      4,
-    )?;
-
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 16,
-      180.0,
-      4,
-    )?;
-
-    memory::write_memory::<f32>(
-      &self.kernel32_lib,
-      self.game_process.as_ref().unwrap().handle,
-      food_addr + 44,
-      080.0,
-      4,
-    )?;
-
-    return Ok(());
+    Ok(())
   }
 }
 
-pub fn load_psapi_library() -> libloading::Library {
+pub fn load_psapi_library() -> Result<libloading::Library, TrainerError> {
   unsafe {
-    libloading::Library::new(r"C:\Windows\System32\psapi.dll").unwrap()
+    Ok(libloading::Library::new(r"C:\Windows\System32\psapi.dll")?)
   }
 }
-pub fn load_kernel32_library() -> libloading::Library {
+pub fn load_kernel32_library() -> Result<libloading::Library, TrainerError> {
   unsafe {
-    libloading::Library::new(r"C:\Windows\System32\kernel32.dll").unwrap()
+    Ok(libloading::Library::new(r"C:\Windows\System32\kernel32.dll")?)
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use memory::{Bitness, BufferMemory};
+
+  /*
+    与测试共享的 BufferMemory，连接之后还可以修改其中的数据
+  */
+  struct SharedMemory(Arc<BufferMemory>);
+
+  impl ProcessMemory for SharedMemory {
+    fn read_bytes(&self, addr: usize, buf: &mut [u8]) -> Result<(), TrainerError> {
+      self.0.read_bytes(addr, buf)
+    }
+
+    fn write_bytes(&self, addr: usize, data: &[u8]) -> Result<(), TrainerError> {
+      self.0.write_bytes(addr, data)
+    }
+
+    fn bitness(&self) -> Bitness {
+      self.0.bitness()
+    }
+
+    fn modules(&self) -> Vec<memory::ModuleInfo> {
+      self.0.modules()
+    }
+  }
+
+  #[test]
+  fn retries_detection_after_transient_error() {
+    let mut winapi = WinApi::try_new().unwrap();
+    let memory = Arc::new(BufferMemory::new());
+    // 执行文件还没有完全映射，读取 PE 头失败
+    memory.map(0x400000, vec![0u8; 0x10]);
+    winapi.attach_memory(process::ProcessItem::new(1, "age2_x1.exe"), Box::new(SharedMemory(memory.clone())));
+    assert!(winapi.layout.is_none());
+
+    let mut header = vec![0u8; 0x1000];
+    header[..2].copy_from_slice(b"MZ");
+    header[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    header[0x80..0x84].copy_from_slice(b"PE\0\0");
+    header[0x84..0x86].copy_from_slice(&0x14Cu16.to_le_bytes());
+    memory.map(0x400000, header);
+    memory.add_module("age2_x1.exe", 0x400000);
+    // 游戏数据还不能读取，但是已经识别出版本
+    assert!(winapi.read_game_info().is_err());
+    assert!(winapi.layout.is_some());
+    assert!(winapi.game_process.is_some());
+  }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::TrainerError;
+use super::memory::{Pod, ProcessMemory, ProcessMemoryExt};
+
+
+/*
+  指针链的起点：绝对地址，相对于某个模块（执行文件、dll）加载基址的偏移，
+  或者相对于内存布局中某个特征码找到的地址的偏移（连接进程时替换为绝对地址）
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PointerBase {
+  Absolute(usize),
+  Module { name: String, offset: usize },
+  Signature { name: String, offset: usize },
+}
+
+/*
+  多级指针：从 base 开始，每一步先读取当前地址中保存的指针，再加上对应的偏移。
+  指针的长度（4 或 8 字节）由目标进程的位数决定。
+  例如食物：[[0x007A5FEC] + 0xA8] + 0x0
+    base    = 0x007A5FEC
+    offsets = [0xA8, 0x0]
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PointerChain {
+  pub base: PointerBase,
+  pub offsets: Vec<usize>,
+}
+
+impl PointerChain {
+  pub fn new(base: usize, offsets: &[usize]) -> Self {
+    Self {
+      base: PointerBase::Absolute(base),
+      offsets: offsets.to_vec(),
+    }
+  }
+
+  pub fn module(name: &str, offset: usize, offsets: &[usize]) -> Self {
+    Self {
+      base: PointerBase::Module { name: name.to_string(), offset },
+      offsets: offsets.to_vec(),
+    }
+  }
+
+  /*
+    复制指针链，并在最后一级偏移上再加上 offset，用于访问同一个结构体中的其它字段
+  */
+  pub fn field(&self, offset: usize) -> Self {
+    let mut chain = self.clone();
+    match chain.offsets.last_mut() {
+      Some(last) => *last = last.wrapping_add(offset),
+      None => match &mut chain.base {
+        PointerBase::Absolute(addr) => *addr = addr.wrapping_add(offset),
+        PointerBase::Module { offset: base_offset, .. } | PointerBase::Signature { offset: base_offset, .. } => {
+          *base_offset = base_offset.wrapping_add(offset)
+        }
+      },
+    }
+    chain
+  }
+
+  /*
+    计算指针链最终指向的地址。
+    第 step 步读到空指针时返回 NullPointer，读取失败时返回 UnreadablePointer
+  */
+  pub fn resolve(&self, memory: &dyn ProcessMemory) -> Result<usize, TrainerError> {
+    let mut addr = match &self.base {
+      PointerBase::Absolute(addr) => *addr,
+      PointerBase::Module { name, offset } => {
+        let base = memory.module_base(name).ok_or_else(|| TrainerError::ModuleNotFound(name.clone()))?;
+        base.wrapping_add(*offset)
+      }
+      PointerBase::Signature { name, .. } => {
+        return Err(TrainerError::Signature(format!("\"{}\" is not resolved", name)));
+      }
+    };
+
+    for (step, offset) in self.offsets.iter().enumerate() {
+      let pointer = match memory.read_pointer(addr) {
+        Ok(pointer) => pointer,
+        Err(TrainerError::AccessDenied(code)) => return Err(TrainerError::AccessDenied(code)),
+        Err(TrainerError::ProcessNotFound(pid)) => return Err(TrainerError::ProcessNotFound(pid)),
+        Err(_) => return Err(TrainerError::UnreadablePointer { step, addr }),
+      };
+      if pointer == 0 {
+        return Err(TrainerError::NullPointer { step });
+      }
+      addr = pointer.wrapping_add(*offset);
+    }
+
+    Ok(addr)
+  }
+
+  pub fn read<T: Pod>(&self, memory: &dyn ProcessMemory) -> Result<T, TrainerError> {
+    let addr = self.resolve(memory)?;
+    memory.read::<T>(addr)
+  }
+
+  pub fn write<T: Pod>(&self, memory: &dyn ProcessMemory, value: T) -> Result<(), TrainerError> {
+    let addr = self.resolve(memory)?;
+    memory.write::<T>(addr, value)
+  }
+}
+
+/*
+  解析指针链的起点：0x7A5FEC、"age2_x1.exe"+0x3A5FEC、age2_x1.exe+3A5FEC，
+  或者 @player_base+0x10（内存布局中名为 player_base 的特征码）。数字都是十六进制，0x 前缀可以省略
+*/
+impl FromStr for PointerBase {
+  type Err = TrainerError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let invalid = || TrainerError::Layout(format!("invalid pointer base \"{}\"", text));
+    let text = text.trim();
+
+    if let Some(signature) = text.strip_prefix('@') {
+      let (name, offset) = match signature.split_once('+') {
+        Some((name, offset)) => (name.trim(), parse_hex(offset).ok_or_else(invalid)?),
+        None => (signature.trim(), 0),
+      };
+      if name.is_empty() {
+        return Err(invalid());
+      }
+      return Ok(PointerBase::Signature { name: name.to_string(), offset });
+    }
+
+    let (name, offset) = if let Some(quoted) = text.strip_prefix('"') {
+      let (name, rest) = quoted.split_once('"').ok_or_else(invalid)?;
+      let rest = rest.trim();
+      let offset = match rest.strip_prefix('+') {
+        Some(offset) => parse_hex(offset).ok_or_else(invalid)?,
+        None if rest.is_empty() => 0,
+        None => return Err(invalid()),
+      };
+      (name, offset)
+    } else if let Some((name, offset)) = text.rsplit_once('+') {
+      let offset = parse_hex(offset).ok_or_else(invalid)?;
+      if let Some(addr) = parse_hex(name) {
+        return Ok(PointerBase::Absolute(addr.wrapping_add(offset)));
+      }
+      (name.trim(), offset)
+    } else if let Some(addr) = parse_hex(text) {
+      return Ok(PointerBase::Absolute(addr));
+    } else {
+      (text, 0)
+    };
+
+    if name.is_empty() {
+      return Err(invalid());
+    }
+    Ok(PointerBase::Module { name: name.to_string(), offset })
+  }
+}
+
+/*
+  解析 Cheat Engine 写法的指针链，与 Display 的输出格式相同，例如 [["age2_x1.exe"+0x3A5FEC]+0xA8]+0x4
+*/
+impl FromStr for PointerChain {
+  type Err = TrainerError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let invalid = || TrainerError::Layout(format!("invalid pointer chain \"{}\"", text));
+    let trimmed = text.trim();
+    let depth = trimmed.chars().take_while(|c| *c == '[').count();
+    let rest = &trimmed[depth..];
+
+    // 起点在第一个 ] 之前，模块名中的 ] 要放在引号里
+    let quote_end = match rest.strip_prefix('"') {
+      Some(quoted) => quoted.find('"').ok_or_else(invalid)? + 2,
+      None => 0,
+    };
+    let base_end = match depth {
+      0 => rest.len(),
+      _ => quote_end + rest[quote_end..].find(']').ok_or_else(invalid)?,
+    };
+    let base = rest[..base_end].parse::<PointerBase>()?;
+
+    let mut offsets = vec![];
+    let mut rest = &rest[base_end..];
+    for _ in 0..depth {
+      rest = rest.trim_start().strip_prefix(']').ok_or_else(invalid)?.trim_start();
+      let end = rest.find(']').unwrap_or(rest.len());
+      let offset = match rest[..end].trim() {
+        "" => 0,
+        offset => parse_hex(offset.strip_prefix('+').ok_or_else(invalid)?).ok_or_else(invalid)?,
+      };
+      offsets.push(offset);
+      rest = &rest[end..];
+    }
+    if !rest.trim().is_empty() {
+      return Err(invalid());
+    }
+
+    Ok(Self { base, offsets })
+  }
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+  let text = text.trim();
+  let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+  usize::from_str_radix(digits, 16).ok()
+}
+
+/*
+  按 Cheat Engine 的写法显示，例如 [[0x7A5FEC]+0xA8]+0x0
+*/
+impl fmt::Display for PointerChain {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut text = match &self.base {
+      PointerBase::Absolute(addr) => format!("0x{:X}", addr),
+      PointerBase::Module { name, offset } => format!("\"{}\"+0x{:X}", name, offset),
+      PointerBase::Signature { name, offset } => format!("@{}+0x{:X}", name, offset),
+    };
+    for offset in &self.offsets {
+      text = format!("[{}]+0x{:X}", text, offset);
+    }
+    f.write_str(&text)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::winapi::memory::{Bitness, BufferMemory};
+
+  #[test]
+  fn resolves_multi_level_chain() {
+    let memory = BufferMemory::new();
+    let mut exe = vec![0u8; 0x100];
+    exe[0x10..0x14].copy_from_slice(&0x2000u32.to_le_bytes());
+    memory.map(0x400000, exe);
+    let mut object = vec![0u8; 0x100];
+    object[0xA8..0xAC].copy_from_slice(&0x3000u32.to_le_bytes());
+    memory.map(0x2000, object);
+
+    assert_eq!(PointerChain::new(0x400010, &[0xA8, 0x4]).resolve(&memory).unwrap(), 0x3004);
+    assert_eq!(PointerChain::new(0x400010, &[]).resolve(&memory).unwrap(), 0x400010);
+    assert!(matches!(
+      PointerChain::new(0x400010, &[0x10, 0x0]).resolve(&memory),
+      Err(TrainerError::NullPointer { step: 1 })
+    ));
+    assert!(matches!(
+      PointerChain::new(0x9000, &[0x0]).resolve(&memory),
+      Err(TrainerError::UnreadablePointer { step: 0, addr: 0x9000 })
+    ));
+  }
+
+  #[test]
+  fn resolves_module_relative_base() {
+    let memory = BufferMemory::with_bitness(Bitness::Bits64);
+    let mut exe = vec![0u8; 0x100];
+    exe[0x20..0x28].copy_from_slice(&0x1_0000_2000u64.to_le_bytes());
+    memory.map(0x140000000, exe);
+    memory.add_module("Game.exe", 0x140000000);
+    memory.map(0x1_0000_2000, vec![0u8; 0x10]);
+
+    assert_eq!(PointerChain::module("game.exe", 0x20, &[0x8]).resolve(&memory).unwrap(), 0x1_0000_2008);
+    assert!(matches!(
+      PointerChain::module("other.dll", 0x20, &[0x8]).resolve(&memory),
+      Err(TrainerError::ModuleNotFound(_))
+    ));
+  }
+
+  #[test]
+  fn parses_pointer_bases() {
+    assert_eq!("0x7A5FEC".parse::<PointerBase>().unwrap(), PointerBase::Absolute(0x7A5FEC));
+    assert_eq!("7A5FEC+10".parse::<PointerBase>().unwrap(), PointerBase::Absolute(0x7A5FFC));
+    assert_eq!(
+      "\"age2_x1.exe\"+0x3A5FEC".parse::<PointerBase>().unwrap(),
+      PointerBase::Module { name: "age2_x1.exe".to_string(), offset: 0x3A5FEC },
+    );
+    assert_eq!(
+      "age2_x1.exe+3A5FEC".parse::<PointerBase>().unwrap(),
+      PointerBase::Module { name: "age2_x1.exe".to_string(), offset: 0x3A5FEC },
+    );
+    assert_eq!(
+      "@player_base+0x10".parse::<PointerBase>().unwrap(),
+      PointerBase::Signature { name: "player_base".to_string(), offset: 0x10 },
+    );
+    assert!("\"age2_x1.exe+0x10".parse::<PointerBase>().is_err());
+    assert!("@".parse::<PointerBase>().is_err());
+  }
+
+  #[test]
+  fn chain_text_round_trips() {
+    let chains = [
+      PointerChain::new(0x7A5FEC, &[0xA8, 0x0]),
+      PointerChain::module("age2_x1.exe", 0x3A5FEC, &[0xA8, 0x4]),
+      PointerChain::module("my]game.exe", 0x10, &[0x0]),
+      PointerChain { base: PointerBase::Signature { name: "world".to_string(), offset: 0x8 }, offsets: vec![0x4C] },
+      PointerChain::module("age2_x1.exe", 0x3912A0, &[]),
+    ];
+    for chain in chains {
+      let text = chain.to_string();
+      assert_eq!(text.parse::<PointerChain>().unwrap(), chain, "{}", text);
+    }
+
+    assert_eq!(
+      "[[\"age2_x1.exe\"+0x3A5FEC]+0xA8]+0x4".parse::<PointerChain>().unwrap(),
+      PointerChain::module("age2_x1.exe", 0x3A5FEC, &[0xA8, 0x4]),
+    );
+    assert_eq!("[[0x7A5FEC]]".parse::<PointerChain>().unwrap(), PointerChain::new(0x7A5FEC, &[0x0, 0x0]));
+    assert!("[0x7A5FEC+0xA8".parse::<PointerChain>().is_err());
+    assert!("[0x7A5FEC]-0x4".parse::<PointerChain>().is_err());
+  }
+}
@@ -1,30 +1,45 @@
-use libloading;
+use std::io;
 use std::path;
 
+use crate::error::TrainerError;
+
 const DWORD_SIZE: u32= 4;
 const PROCESS_QUERY_INFORMATION: u32 = 0x0400;
 const PROCESS_VM_OPERATION: u32 = 0x0008;
 const PROCESS_VM_READ: u32 = 0x0010;
 const PROCESS_VM_WRITE: u32 = 0x0020;
+const ERROR_ACCESS_DENIED: i32 = 5;
+const ERROR_INVALID_PARAMETER: i32 = 87;
 
 
 #[derive(Debug, Clone)]
 pub struct ProcessItem {
   pub pid: u32,
   pub name: String,
-  pub(crate) handle: u32,
+  pub(crate) handle: usize,
 }
 
 impl ProcessItem {
-    pub fn from_pid(pid: u32, psapi_lib: &libloading::Library, kernel32_lib: &libloading::Library) -> Result<ProcessItem, Box<dyn std::error::Error>> {
+    /*
+      只有进程号和进程名，没有打开进程句柄
+    */
+    pub fn new(pid: u32, name: &str) -> Self {
+      Self {
+        pid,
+        name: name.to_string(),
+        handle: 0,
+      }
+    }
+
+    pub fn from_pid(pid: u32, psapi_lib: &libloading::Library, kernel32_lib: &libloading::Library) -> Result<ProcessItem, TrainerError> {
       unsafe {
-        let open_func = kernel32_lib.get::<unsafe extern "system" fn(u32, bool, u32) -> u32>(b"OpenProcess\0").unwrap();
-        let handle = open_func(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid);
+        let open_func = kernel32_lib.get::<unsafe extern "system" fn(u32, i32, u32) -> usize>(b"OpenProcess\0")?;
+        let handle = open_func(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, 0, pid);
         if handle == 0 {
-          return Err(format!("Failed to open process: {}", pid).into());
+          return Err(open_process_error(pid));
         }
 
-        let get_name_func = psapi_lib.get::<unsafe extern "system" fn(u32, *mut u32, u32) -> u32>(b"GetProcessImageFileNameA\0").unwrap();
+        let get_name_func = psapi_lib.get::<unsafe extern "system" fn(usize, *mut u32, u32) -> u32>(b"GetProcessImageFileNameA\0")?;
         let mut name_buf: [u8; 2024] = [0; 2024];
         let ret = get_name_func(handle, name_buf.as_mut_ptr() as *mut u32, 2024);
 
@@ -38,8 +53,8 @@ impl ProcessItem {
 
         Ok(Self {
           pid,
-          name: name,
-          handle: handle
+          name,
+          handle,
         })
       }
     }
@@ -49,26 +64,26 @@ impl ProcessItem {
         return;
       }
       unsafe {
-        let close_func = kernel32_lib.get::<unsafe extern "system" fn(u32) -> u32>(b"CloseHandle\0").unwrap();
+        let close_func = kernel32_lib.get::<unsafe extern "system" fn(usize) -> i32>(b"CloseHandle\0").unwrap();
         close_func(self.handle);
         self.handle = 0;
       }
     }
 
-    pub fn inject(&mut self, kernel32_lib: &libloading::Library) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn inject(&mut self, kernel32_lib: &libloading::Library) -> Result<(), TrainerError> {
       if self.handle != 0 {
         return Ok(());
       }
 
       unsafe {
-        let open_func = kernel32_lib.get::<unsafe extern "system" fn(u32, bool, u32) -> u32>(b"OpenProcess\0").unwrap();
+        let open_func = kernel32_lib.get::<unsafe extern "system" fn(u32, i32, u32) -> usize>(b"OpenProcess\0")?;
         let handle = open_func(
           PROCESS_QUERY_INFORMATION|PROCESS_VM_READ|PROCESS_VM_OPERATION|PROCESS_VM_WRITE,
-          false,
+          0,
           self.pid
         );
         if handle == 0 {
-          return Err(format!("Failed to open process: {}", self.pid).into());
+          return Err(open_process_error(self.pid));
         }
 
         self.handle = handle;
@@ -78,6 +93,18 @@ impl ProcessItem {
     }
 }
 
+/*
+  把 OpenProcess 失败时的系统错误转换为 TrainerError
+*/
+fn open_process_error(pid: u32) -> TrainerError {
+  let err = io::Error::last_os_error();
+  match err.raw_os_error() {
+    Some(ERROR_ACCESS_DENIED) => TrainerError::AccessDenied(ERROR_ACCESS_DENIED),
+    Some(ERROR_INVALID_PARAMETER) => TrainerError::ProcessNotFound(pid),
+    _ => TrainerError::Io(err),
+  }
+}
+
 
 #[derive(Debug, Default)]
 pub struct ProcessManager {
@@ -96,8 +123,7 @@ impl ProcessManager {
       }
 
       let num_processes = bytes_returned / DWORD_SIZE;
-      for i in 0..num_processes as usize {
-        let pid = processes[i];
+      for &pid in &processes[..num_processes as usize] {
         if pid == 0 {
           continue;
         }
@@ -112,21 +138,28 @@ impl ProcessManager {
       }
     };
 
-    return list;
+    list
   }
 
   pub fn find_game_process(&self, psapi_lib: &libloading::Library, kernel32_lib: &libloading::Library) -> Option<ProcessItem> {
     let list = self.get_process_list(psapi_lib, kernel32_lib);
+    self.find_game_process_in(list, |_| false)
+  }
 
-    let mut game_process: Option<ProcessItem> = None;
+  /*
+    从进程列表中找出游戏进程：执行文件名为 age2*.exe，或者 is_image_name 返回 true（例如版本配置中的执行文件）。
+    Windows 和 Linux(Wine) 下的进程列表都使用同样的规则
+  */
+  pub fn find_game_process_in(&self, list: Vec<ProcessItem>, is_image_name: impl Fn(&str) -> bool) -> Option<ProcessItem> {
     let current_pid = std::process::id();
-    for p in list {
-      if p.name.starts_with("age2") && p.name.ends_with(".exe") && p.pid != current_pid {
-        game_process = Some(p);
-        break;
-      }
-    }
-
-    return game_process;
+    list.into_iter().find(|p| p.pid != current_pid && (is_game_process_name(&p.name) || is_image_name(&p.name)))
   }
-}
\ No newline at end of file
+}
+
+/*
+  游戏的执行文件名为 age2*.exe，例如 age2_x1.exe
+*/
+pub fn is_game_process_name(name: &str) -> bool {
+  let name = name.to_lowercase();
+  name.starts_with("age2") && name.ends_with(".exe")
+}
@@ -0,0 +1,306 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use crate::cheat;
+use crate::config::CheatDef;
+use crate::error::TrainerError;
+use crate::freeze::{self, FreezeEngine, FreezeEntry, FreezeTask};
+use crate::game::GameInfo;
+use crate::unit::UnitInfo;
+use crate::winapi::WinApi;
+use crate::winapi::process::ProcessManager;
+
+// 读取游戏数据的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// 没有连接游戏进程时，查找游戏进程的间隔。查找时会打开系统中的每个进程，不能太频繁
+const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+
+
+/*
+  游戏的状态
+*/
+#[derive(Debug, Clone)]
+pub enum GameStatus {
+  // 游戏程序未运行
+  NotFound,
+  // 连接或者读取游戏进程时出错，错误不能复制，用 Arc 共享
+  Error(Arc<TrainerError>),
+  // 读取到的游戏数据，is_running 为 false 时还未开始游戏
+  Ready(GameInfo),
+}
+
+/*
+  后台任务发布的最新状态，界面只读取它，不直接访问游戏进程
+*/
+#[derive(Debug, Clone)]
+pub struct GameSnapshot {
+  pub status: GameStatus,
+  // 锁定引擎中每个字段的状态和写入统计
+  pub freezes: Vec<FreezeEntry>,
+  // 当前使用的修改配置
+  pub cheats: Vec<CheatDef>,
+  // 修改的玩家在玩家数组中的下标，None 为本地玩家
+  pub player: Option<usize>,
+  // 修改的玩家的单位，没有开启单位列表时为 None
+  pub units: Option<Result<Vec<UnitInfo>, Arc<TrainerError>>>,
+}
+
+impl Default for GameSnapshot {
+  fn default() -> Self {
+    Self { status: GameStatus::NotFound, freezes: vec![], cheats: vec![], player: None, units: None }
+  }
+}
+
+/*
+  界面发给后台任务的命令
+*/
+#[derive(Debug, Clone)]
+pub enum WorkerCommand {
+  // 修改字段的配置，立即生效
+  SetCheat(CheatDef),
+  // 开启或关闭某个字段的修改，以后台任务中的配置为准
+  ToggleCheat(String),
+  // 修改另一个玩家，None 为本地玩家
+  SelectPlayer(Option<usize>),
+  // 开启或关闭单位列表，读取单位的开销较大，只在界面显示时读取
+  ShowUnits(bool),
+  // 恢复开关类修改写入的值，然后停止
+  Shutdown,
+}
+
+/*
+  在后台线程中连接、读取游戏进程，通过 watch 通道发布 GameSnapshot。
+  查找进程和读取游戏数据都是阻塞的调用，所以不放在 tokio 的任务中。
+  被 drop 时恢复开关类修改写入的值，然后停止线程
+*/
+pub struct GameWorker {
+  thread: Option<JoinHandle<()>>,
+  snapshot: watch::Receiver<GameSnapshot>,
+  commands: mpsc::Sender<WorkerCommand>,
+}
+
+impl GameWorker {
+  /*
+    需要在 tokio 运行时中调用，锁定引擎的写入任务在运行时中执行
+  */
+  pub fn spawn(winapi: WinApi) -> Self {
+    let snapshot = GameSnapshot { cheats: winapi.config.cheats.clone(), ..GameSnapshot::default() };
+    let (sender, snapshot) = watch::channel(snapshot);
+    let (commands, receiver) = mpsc::channel();
+    let runtime = tokio::runtime::Handle::current();
+    let thread = std::thread::spawn(move || {
+      let _guard = runtime.enter();
+      run(winapi, sender, receiver);
+    });
+    Self { thread: Some(thread), snapshot, commands }
+  }
+
+  /*
+    修改字段的配置，后台任务在下一次读取游戏数据时生效
+  */
+  pub fn set_cheat(&self, cheat: CheatDef) {
+    // 后台任务已经停止时忽略
+    let _ = self.commands.send(WorkerCommand::SetCheat(cheat));
+  }
+
+  /*
+    开启或关闭某个字段的修改。界面的状态可能还没有更新，由后台任务按它自己的配置切换
+  */
+  pub fn toggle_cheat(&self, field: &str) {
+    let _ = self.commands.send(WorkerCommand::ToggleCheat(field.to_string()));
+  }
+
+  /*
+    停止后台任务，停止之前恢复开关类修改写入的值。最多等待 1 秒
+  */
+  pub async fn shutdown(mut self) {
+    let Some(thread) = self.thread.take() else {
+      return;
+    };
+    if self.commands.send(WorkerCommand::Shutdown).is_ok() {
+      let join = tokio::task::spawn_blocking(move || thread.join());
+      let _ = tokio::time::timeout(Duration::from_secs(1), join).await;
+    }
+  }
+
+  /*
+    开启或关闭单位列表
+  */
+  pub fn show_units(&self, show: bool) {
+    let _ = self.commands.send(WorkerCommand::ShowUnits(show));
+  }
+
+  /*
+    选择要修改的玩家，None 为本地玩家
+  */
+  pub fn select_player(&self, player: Option<usize>) {
+    let _ = self.commands.send(WorkerCommand::SelectPlayer(player));
+  }
+
+  /*
+    最新的游戏状态
+  */
+  pub fn snapshot(&self) -> GameSnapshot {
+    self.snapshot.borrow().clone()
+  }
+
+  /*
+    订阅状态的变化
+  */
+  pub fn subscribe(&self) -> watch::Receiver<GameSnapshot> {
+    self.snapshot.clone()
+  }
+}
+
+/*
+  停止锁定引擎之前恢复开关类修改写入的值
+*/
+fn stop_freezer(freezer: &mut Option<Freezer>, winapi: &WinApi) {
+  if let (Some(freezer), Some(memory)) = (freezer.take(), winapi.memory()) {
+    // 游戏进程可能已经退出，恢复失败时忽略
+    let _ = freezer.engine.lock().unwrap().restore(memory);
+  }
+}
+
+/*
+  没有调用 shutdown 时（例如界面出错退出），同样先恢复开关类修改写入的值
+*/
+/*
+  修改字段的配置，同时更新锁定引擎。开关类修改不支持的修改方式或者数值时保持原来的配置
+*/
+fn set_cheat(winapi: &mut WinApi, freezer: &Option<Freezer>, cheat: CheatDef) {
+  if winapi.check_cheat(&cheat).is_err() {
+    return;
+  }
+  if let Some(freezer) = freezer {
+    freezer.engine.lock().unwrap().set(&cheat.field, cheat.freeze_mode(), cheat.interval());
+  }
+  winapi.config.set_cheat(cheat);
+}
+
+impl Drop for GameWorker {
+  fn drop(&mut self) {
+    if let Some(thread) = self.thread.take()
+      && self.commands.send(WorkerCommand::Shutdown).is_ok() {
+      let _ = thread.join();
+    }
+  }
+}
+
+/*
+  连接游戏进程之后的锁定引擎和写入任务
+*/
+struct Freezer {
+  engine: Arc<Mutex<FreezeEngine>>,
+  _task: FreezeTask,
+}
+
+fn run(
+  mut winapi: WinApi,
+  sender: watch::Sender<GameSnapshot>,
+  commands: mpsc::Receiver<WorkerCommand>,
+) {
+  let manager = ProcessManager::default();
+  let mut freezer: Option<Freezer> = None;
+  let mut player: Option<usize> = None;
+  let mut show_units = false;
+  let mut last_search: Option<Instant> = None;
+  let mut next_poll = Instant::now();
+
+  loop {
+    // 等待下一次读取游戏数据，期间收到的命令立即处理
+    let wait = next_poll.saturating_duration_since(Instant::now());
+    match commands.recv_timeout(wait) {
+      Ok(command) => match command {
+        WorkerCommand::SetCheat(cheat) => set_cheat(&mut winapi, &freezer, cheat),
+        WorkerCommand::ToggleCheat(field) => {
+          if let Some(cheat) = winapi.config.cheats.iter().find(|cheat| cheat.field == field) {
+            let cheat = CheatDef { enabled: !cheat.enabled, ..cheat.clone() };
+            set_cheat(&mut winapi, &freezer, cheat);
+          }
+        }
+        WorkerCommand::SelectPlayer(index) if index != player => {
+          player = index;
+          // 重新创建锁定引擎，写入新选择的玩家
+          stop_freezer(&mut freezer, &winapi);
+        }
+        WorkerCommand::SelectPlayer(_) => {}
+        WorkerCommand::ShowUnits(show) => show_units = show,
+        WorkerCommand::Shutdown => {
+          stop_freezer(&mut freezer, &winapi);
+          return;
+        }
+      },
+      Err(RecvTimeoutError::Timeout) => {}
+      // GameWorker 已经被 drop
+      Err(RecvTimeoutError::Disconnected) => {
+        stop_freezer(&mut freezer, &winapi);
+        return;
+      }
+    }
+    if Instant::now() < next_poll {
+      continue;
+    }
+    next_poll = Instant::now() + POLL_INTERVAL;
+
+    let status = poll(&mut winapi, &manager, &mut last_search);
+
+    // 识别出游戏版本后启动锁定引擎，断开游戏进程后停止
+    let attached = winapi.layout.as_ref().zip(winapi.shared_memory());
+    match (attached, &freezer) {
+      (Some((layout, memory)), None) => {
+        let mut engine = FreezeEngine::for_player(layout, player, memory.bitness().pointer_size());
+        cheat::apply_cheats(&mut engine, &winapi.config.cheats);
+        let engine = Arc::new(Mutex::new(engine));
+        let task = freeze::spawn(engine.clone(), memory);
+        freezer = Some(Freezer { engine, _task: task });
+      }
+      (None, Some(_)) => freezer = None,
+      _ => {}
+    }
+
+    let Some(status) = status else {
+      continue;
+    };
+    let freezes = freezer.as_ref().map_or(vec![], |freezer| freezer.engine.lock().unwrap().entries().to_vec());
+    let cheats = winapi.config.cheats.clone();
+    let units = match &status {
+      GameStatus::Ready(info) if show_units && info.is_running => Some(winapi.read_units(player).map_err(Arc::new)),
+      _ => None,
+    };
+    // 界面已经退出时停止
+    if sender.send(GameSnapshot { status, freezes, cheats, player, units }).is_err() {
+      stop_freezer(&mut freezer, &winapi);
+      break;
+    }
+  }
+}
+
+/*
+  没有连接游戏进程时每隔 SEARCH_INTERVAL 查找一次，连接之后读取游戏数据。
+  还没到查找时间时返回 None，不更新状态
+*/
+fn poll(winapi: &mut WinApi, manager: &ProcessManager, last_search: &mut Option<Instant>) -> Option<GameStatus> {
+  if winapi.game_process.is_none() {
+    if last_search.is_some_and(|last| last.elapsed() < SEARCH_INTERVAL) {
+      return None;
+    }
+    *last_search = Some(Instant::now());
+
+    let Some(game_process) = winapi.find_game_process(manager) else {
+      return Some(GameStatus::NotFound);
+    };
+    if let Err(e) = winapi.set_game_process(game_process) {
+      return Some(GameStatus::Error(Arc::new(e)));
+    }
+  }
+
+  match winapi.read_game_info() {
+    Ok(info) => Some(GameStatus::Ready(info)),
+    Err(e) => Some(GameStatus::Error(Arc::new(e))),
+  }
+}